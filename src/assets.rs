@@ -0,0 +1,173 @@
+//! Static asset fingerprinting, integrity hashes, and the template functions
+//! that expose them.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use base64::Engine;
+use sha2::{Digest, Sha384};
+
+use crate::{config, util, Error, Result};
+
+/// The number of hex digits of the content hash to put in fingerprinted file
+/// names.
+pub(crate) const HASH_LEN: usize = 8;
+
+/// Maps logical static asset paths, relative to the static directory (eg
+/// `css/style.css`), to the paths they are written to, relative to the out
+/// directory (eg `css/style.3fa9c1d2.css`).
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(transparent)]
+pub(crate) struct Manifest(BTreeMap<String, String>);
+
+impl Manifest {
+    /// Builds the manifest for every file in the static directory `dir`.
+    /// Assets matched by the `fingerprint` settings' `include` (and not
+    /// `exclude`) globs are fingerprinted if it is enabled, and every other
+    /// asset maps to itself.
+    pub(crate) fn build(dir: &Path, fingerprint: &config::Fingerprint) -> Result<Self> {
+        let mut res = BTreeMap::new();
+
+        if !dir.exists() {
+            return Ok(Self(res));
+        }
+
+        let include = util::glob_set(&fingerprint.include)?;
+        let exclude = util::glob_set(&fingerprint.exclude)?;
+
+        for asset in util::walk_dir(dir)? {
+            let logical = util::swap_prefix(&asset, dir, "")?;
+
+            let output = if fingerprint.enable
+                && include.is_match(&logical)
+                && !exclude.is_match(&logical)
+            {
+                let contents = fs::read(&asset).map_err(|source| Error::Io {
+                    msg: format!("Cannot read static asset '{}'", asset.display()),
+                    source,
+                })?;
+
+                fingerprinted(&logical, &contents)
+            } else {
+                logical.clone()
+            };
+
+            log::debug!(
+                "Asset '{}' maps to '{}'",
+                logical.display(),
+                output.display()
+            );
+
+            res.insert(util::to_url_path(logical)?, util::to_url_path(output)?);
+        }
+
+        Ok(Self(res))
+    }
+
+    /// Returns the output path for a logical asset path, if the asset exists.
+    pub(crate) fn get(&self, logical: &str) -> Option<&str> {
+        self.0
+            .get(logical.trim_start_matches('/'))
+            .map(String::as_str)
+    }
+
+    /// Writes the manifest to `path` as JSON, unless this is a dry run.
+    pub(crate) fn write(&self, path: &Path, dry_run: bool) -> Result<()> {
+        log::info!(
+            "{} asset manifest '{}'",
+            util::write_verb(path, dry_run),
+            path.display()
+        );
+
+        let json = serde_json::to_string_pretty(self)
+            .expect("Serializing a map of strings should be infallible");
+
+        util::write_file(path, json, dry_run)
+    }
+}
+
+/// Returns `path` with a hash of `contents` inserted before its extension, eg
+/// `style.css` becomes `style.3fa9c1d2.css`.
+fn fingerprinted(path: &Path, contents: &[u8]) -> PathBuf {
+    let hash = util::hex_digest(contents);
+    let hash = &hash[..HASH_LEN];
+
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(".");
+    name.push(hash);
+
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+
+    path.with_file_name(name)
+}
+
+/// Returns the `asset_url` template function, which maps a logical asset path
+/// to its (possibly fingerprinted) url.
+///
+/// ## Example
+/// ```html
+/// <link rel="stylesheet" href="{{ asset_url(path="style.css") }}" />
+/// ```
+pub(crate) fn asset_url(manifest: Manifest, prefix: String) -> impl tera::Function {
+    move |args: &HashMap<String, tera::Value>| {
+        let path = args
+            .get("path")
+            .and_then(tera::Value::as_str)
+            .ok_or_else(|| tera::Error::msg("`asset_url` requires a string `path` argument"))?;
+
+        match manifest.get(path) {
+            Some(output) => Ok(tera::Value::String(prefix.clone() + output)),
+            None => Err(tera::Error::msg(format!(
+                "`asset_url`: no static asset '{path}'"
+            ))),
+        }
+    }
+}
+
+/// Returns the `sri` template function, which computes a [Subresource
+/// Integrity](https://developer.mozilla.org/en-US/docs/Web/Security/Subresource_Integrity)
+/// hash for a file in the static directory or, failing that, the out
+/// directory.
+///
+/// ## Example
+/// ```html
+/// <script src="/js/app.js" integrity="{{ sri(path="js/app.js") }}"></script>
+/// ```
+pub(crate) fn sri(dirs: config::Dirs) -> impl tera::Function {
+    move |args: &HashMap<String, tera::Value>| {
+        let path = args
+            .get("path")
+            .and_then(tera::Value::as_str)
+            .ok_or_else(|| tera::Error::msg("`sri` requires a string `path` argument"))?;
+        let relative = path.trim_start_matches('/');
+
+        let file = [&dirs.r#static, &dirs.out]
+            .into_iter()
+            .map(|dir| dir.join(relative))
+            .find(|file| file.is_file())
+            .ok_or_else(|| {
+                tera::Error::msg(format!(
+                    "`sri`: no file '{path}' in '{}' or '{}'",
+                    dirs.r#static.display(),
+                    dirs.out.display()
+                ))
+            })?;
+
+        let contents = fs::read(&file).map_err(|e| {
+            tera::Error::chain(format!("`sri`: cannot read '{}'", file.display()), e)
+        })?;
+
+        log::debug!("Computing integrity hash of '{}'", file.display());
+
+        Ok(tera::Value::String(format!(
+            "sha384-{}",
+            base64::engine::general_purpose::STANDARD.encode(Sha384::digest(contents))
+        )))
+    }
+}
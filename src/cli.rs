@@ -1,8 +1,20 @@
 //! Command-line interface definition and argument handling.
 
+use std::{
+    env,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use crate::{Error, Result};
+
 pub(crate) mod build;
+mod check;
 mod clean;
+mod config;
+mod explain;
 mod init;
+mod list;
 mod new;
 
 /// A file format-agnostic static site generator.
@@ -20,20 +32,89 @@ pub(crate) struct Args {
     /// What level of logging to enable (error, warn, info, debug, or trace).
     #[arg(long, default_value = "info")]
     pub(crate) log_level: log::LevelFilter,
+
+    /// Where to find the config file.
+    #[command(flatten)]
+    pub(crate) project: Project,
+}
+
+impl Args {
+    /// Changes to the project directory, if one was given, and runs the
+    /// subcommand.
+    pub(crate) fn run(self) -> Result<()> {
+        if let Some(dir) = &self.project.project_dir {
+            env::set_current_dir(dir).map_err(|source| Error::Io {
+                msg: format!("Cannot change directory to '{}'", dir.display()),
+                source,
+            })?;
+        }
+
+        self.command.run(&self.project)
+    }
+}
+
+/// Options for finding and loading the config file, shared by every
+/// subcommand.
+#[derive(clap::Args)]
+pub(crate) struct Project {
+    /// Run as if mksite was started in this directory.
+    #[arg(short = 'C', long, global = true)]
+    pub(crate) project_dir: Option<PathBuf>,
+
+    /// The config file to use, instead of the first `mksite.toml` found in the
+    /// current directory or its parents. Paths in the config are relative to
+    /// the directory it is in.
+    #[arg(long, global = true)]
+    pub(crate) config: Option<PathBuf>,
+
+    /// The config profile to use, from a `[profile.<name>]` table in
+    /// `mksite.toml`.
+    #[arg(long, global = true, env = "MKSITE_ENV")]
+    pub(crate) profile: Option<String>,
+}
+
+impl Project {
+    /// Loads the config file, using the given file and profile, if any.
+    pub(crate) fn load(&self) -> Result<crate::config::Config> {
+        crate::config::load(self.config.as_deref(), self.profile.as_deref())
+    }
+
+    /// Reads the config file like [Project::load], but without warning about
+    /// or validating it.
+    pub(crate) fn read(&self) -> Result<crate::config::Loaded> {
+        crate::config::read(self.config.as_deref(), self.profile.as_deref())
+    }
 }
 
 /// Enum of subcommands.
 #[derive(clap::Subcommand)]
 pub(crate) enum Command {
     /// Build the site according to `mksite.toml`.
-    Build,
+    Build(build::Args),
+
+    /// Check the built site for broken links.
+    Check(check::Args),
 
     /// Delete all build outputs.
     Clean,
 
+    /// Inspect and check the config file.
+    Config {
+        /// The config subcommand to run.
+        #[command(subcommand)]
+        command: config::Command,
+    },
+
+    /// Explain how a source page, static asset, or output file is built.
+    Explain(explain::Args),
+
     /// Initialize a `mksite.toml` file in the current directory.
     Init,
-    
+
+    /// List the files a build would write, without writing anything.
+    #[command(visible_alias = "routes")]
+    List(list::Args),
+
     /// Scaffold an empty site in a new directory.
     New {
         /// The name of the directory to create.
@@ -42,13 +123,27 @@ pub(crate) enum Command {
 }
 
 impl Command {
-    /// Runs the given command.
-    pub(crate) fn run(self) -> crate::Result<()> {
+    /// Runs the given command, loading the config as described by `project`.
+    pub(crate) fn run(self, project: &Project) -> Result<()> {
         match self {
-            Self::Build => build::cmd(),
-            Self::Clean => clean::cmd(),
+            Self::Build(args) => build::cmd(args, project),
+            Self::Check(args) => check::cmd(args, project),
+            Self::Clean => clean::cmd(project),
+            Self::Config { command } => command.run(project),
+            Self::Explain(args) => explain::cmd(args, project),
             Self::Init => init::cmd(),
+            Self::List(args) => list::cmd(args, project),
             Self::New { name } => new::cmd(name),
         }
     }
 }
+
+/// Writes `output` to stdout.
+fn print(output: &str) -> Result<()> {
+    io::stdout()
+        .write_all(output.as_bytes())
+        .map_err(|source| Error::Io {
+            msg: "Cannot write to stdout".into(),
+            source,
+        })
+}
@@ -1,11 +1,106 @@
 //! The `mksite build` subcommand.
 
-use crate::{config, site};
+use crate::{cli, site, util};
 
 use crate::Result;
 
+/// Arguments to the `mksite build` subcommand.
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Check the built site for broken links afterwards. Links to other sites
+    /// are only checked if `links.external` is set.
+    #[arg(long, conflicts_with = "dry_run")]
+    pub(crate) check_links: bool,
+
+    /// Report what would be written, overwritten, or copied, without writing
+    /// anything or running transforms.
+    #[arg(short = 'n', long)]
+    pub(crate) dry_run: bool,
+
+    /// Run transforms in a dry run, to check that they succeed and to detect
+    /// redirects declared in transformed pages.
+    #[arg(long, requires = "dry_run")]
+    pub(crate) run_transforms: bool,
+
+    /// Delete files in the out directory that this build did not write,
+    /// except those matched by `prune.keep`, even if `prune.enable` is not set.
+    #[arg(long)]
+    pub(crate) prune: bool,
+
+    /// Delete the out directory before building, like `mksite clean`.
+    #[arg(long, conflicts_with = "dry_run")]
+    pub(crate) clean_first: bool,
+
+    /// Which pages to build.
+    #[command(flatten)]
+    pub(crate) pages: Pages,
+}
+
+/// Arguments choosing which pages are built, shared by subcommands that
+/// render the site.
+#[derive(clap::Args)]
+pub(crate) struct Pages {
+    /// Also build draft pages, ie pages in `_drafts` directories or with a
+    /// `<meta name="draft">` tag.
+    #[arg(long)]
+    pub(crate) drafts: bool,
+
+    /// Also build pages whose publication date is in the future.
+    #[arg(long)]
+    pub(crate) future: bool,
+
+    /// The time to compare publication dates against, instead of the current
+    /// time (eg `2024-01-31` or `2024-01-31T12:00:00Z`).
+    #[arg(long, value_parser = parse_now)]
+    pub(crate) now: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Pages {
+    /// Returns the site options for building these pages.
+    pub(crate) fn options(&self) -> site::Options {
+        site::Options {
+            drafts: self.drafts,
+            future: self.future,
+            now: self.now,
+            ..Default::default()
+        }
+    }
+}
+
+/// Parses the argument to `--now`.
+fn parse_now(text: &str) -> std::result::Result<chrono::DateTime<chrono::Utc>, String> {
+    util::parse_date(text).ok_or_else(|| format!("`{text}' is not a YYYY-MM-DD or RFC 3339 date"))
+}
+
 /// Loads all the templates in the `src/` directory and renders them using the
 /// metadata defined in `mksite.toml`.
-pub(crate) fn cmd() -> Result<()> {
-    site::Site::new(config::load()?)?.build()
+pub(crate) fn cmd(args: Args, project: &cli::Project) -> Result<()> {
+    let config = project.load()?;
+    let external = config.links.external;
+
+    if args.clean_first {
+        super::clean::remove(&config.dirs.out)?;
+    }
+
+    let mut site = site::Site::new(
+        config,
+        site::Options {
+            dry_run: args.dry_run,
+            run_transforms: args.run_transforms,
+            prune: args.prune,
+            ..args.pages.options()
+        },
+    )?;
+
+    site.build()?;
+
+    if args.dry_run {
+        log::info!("Dry run complete, nothing was written");
+    }
+
+    if args.check_links {
+        site.check_links(external)?;
+    }
+
+    Ok(())
 }
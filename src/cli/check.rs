@@ -0,0 +1,19 @@
+//! The `mksite check` subcommand.
+
+use crate::{cli, site, Result};
+
+/// Arguments to the `mksite check` subcommand.
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Also check links to other sites, even if `links.external` is not set.
+    #[arg(long)]
+    pub(crate) external: bool,
+}
+
+/// Checks every html page in the `out/` directory for broken links.
+pub(crate) fn cmd(args: Args, project: &cli::Project) -> Result<()> {
+    let config = project.load()?;
+    let external = args.external || config.links.external;
+
+    site::Site::new(config, site::Options::default())?.check_links(external)
+}
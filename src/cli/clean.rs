@@ -1,22 +1,26 @@
 //! The `mksite clean` subcommand.
 
-use std::{fs, io};
+use std::{fs, io, path::Path};
 
-use crate::{config, Result};
+use crate::{cli, Result};
 
 /// Deletes the `out/` directory and all its contents.
-pub(crate) fn cmd() -> Result<()> {
-    let config = config::load()?;
+pub(crate) fn cmd(project: &cli::Project) -> Result<()> {
+    remove(&project.load()?.dirs.out)
+}
 
-    log::info!("Removing '{}/'", config.dirs.out.display());
+/// Deletes the out directory `out` and all its contents, warning if it does
+/// not exist.
+pub(crate) fn remove(out: &Path) -> Result<()> {
+    log::info!("Removing '{}/'", out.display());
 
-    fs::remove_dir_all(&config.dirs.out).or_else(|e| match e.kind() {
+    fs::remove_dir_all(out).or_else(|e| match e.kind() {
         io::ErrorKind::NotFound => {
-            log::warn!("Cannot remove '{}': {e}", config.dirs.out.display());
+            log::warn!("Cannot remove '{}': {e}", out.display());
             Ok(())
         }
         _ => Err(crate::Error::Io {
-            msg: format!("Cannot remove '{}'", config.dirs.out.display()),
+            msg: format!("Cannot remove '{}'", out.display()),
             source: e,
         }),
     })
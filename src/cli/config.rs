@@ -0,0 +1,163 @@
+//! The `mksite config` subcommands.
+
+use std::collections::BTreeMap;
+
+use toml_edit::Item;
+
+use crate::{cli, Error, Result};
+
+/// Subcommands for working with the config file.
+#[derive(clap::Subcommand)]
+pub(crate) enum Command {
+    /// Check `mksite.toml` for unknown keys and invalid settings, without
+    /// building the site.
+    Check,
+
+    /// Print the config with defaults applied, and includes, the profile, and
+    /// environment variables resolved.
+    Show {
+        /// The format to print the config in.
+        #[arg(long, value_enum, default_value = "toml")]
+        format: Format,
+
+        /// Also print where each setting came from: the file, line, and column
+        /// it is set at, or `default`.
+        #[arg(long)]
+        origins: bool,
+    },
+
+    /// Print a JSON Schema for `mksite.toml`, for editors and linters.
+    Schema,
+}
+
+/// Formats `mksite config show` can print the config in.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub(crate) enum Format {
+    /// TOML, with origins as comments.
+    Toml,
+
+    /// JSON, with origins in a separate object keyed by dotted setting paths.
+    Json,
+}
+
+impl Command {
+    /// Runs the given config subcommand.
+    pub(crate) fn run(self, project: &cli::Project) -> Result<()> {
+        match self {
+            Self::Check => check(project),
+            Self::Show { format, origins } => show(project, format, origins),
+            Self::Schema => cli::print(
+                &(serde_json::to_string_pretty(&crate::config::schema())
+                    .expect("Serializing the schema should be infallible")
+                    + "\n"),
+            ),
+        }
+    }
+}
+
+/// Reads and validates the config, failing if it or any of its profiles has
+/// unknown keys.
+fn check(project: &cli::Project) -> Result<()> {
+    let loaded = project.read()?;
+
+    loaded.warn_unknown();
+
+    let in_profiles = loaded.unknown_in_profiles()?;
+    for key in &in_profiles {
+        log::warn!("Unknown key {key}");
+    }
+
+    loaded.config.validate()?;
+
+    let unknown = loaded.unknown.len() + in_profiles.len();
+
+    if unknown > 0 {
+        return Err(Error::Config(format!(
+            "Found {unknown} unknown key{}",
+            if unknown != 1 { "s" } else { "" }
+        )));
+    }
+
+    log::info!("No problems found");
+
+    Ok(())
+}
+
+/// Prints the resolved config in the given format, optionally with the origin
+/// of each setting.
+fn show(project: &cli::Project, format: Format, origins: bool) -> Result<()> {
+    let loaded = project.read()?;
+    loaded.warn_unknown();
+
+    // going through a `toml::Value` sorts the keys of maps like `data`, which
+    // would otherwise be printed in random order
+    let toml = toml::Value::try_from(&loaded.config)
+        .and_then(|value| toml::to_string(&value))
+        .expect("Serializing the config should be infallible");
+    let mut document = toml
+        .parse::<toml_edit::DocumentMut>()
+        .expect("The serialized config should be valid TOML");
+
+    let mut found = BTreeMap::new();
+    if origins {
+        annotate(
+            document.as_table_mut(),
+            &mut Vec::new(),
+            &loaded,
+            &mut found,
+        );
+    }
+
+    let output = match format {
+        Format::Toml => document.to_string(),
+        Format::Json => {
+            let config = serde_json::to_value(&loaded.config)
+                .expect("Serializing the config should be infallible");
+
+            let json = if origins {
+                serde_json::json!({ "config": config, "origins": found })
+            } else {
+                config
+            };
+
+            serde_json::to_string_pretty(&json).expect("Serializing JSON should be infallible")
+                + "\n"
+        }
+    };
+
+    cli::print(&output)
+}
+
+/// Adds a comment with the origin of every setting in `table`, found at the
+/// key path `key`, and collects the origins into `found` by dotted key path.
+/// Arrays and inline tables are treated as single settings, since TOML
+/// doesn't allow comments inside inline tables.
+fn annotate(
+    table: &mut toml_edit::Table,
+    key: &mut Vec<String>,
+    loaded: &crate::config::Loaded,
+    found: &mut BTreeMap<String, String>,
+) {
+    for (name, item) in table.iter_mut() {
+        key.push(name.get().to_owned());
+
+        match item {
+            Item::Table(table) => annotate(table, key, loaded, found),
+            Item::ArrayOfTables(tables) => {
+                for (i, table) in tables.iter_mut().enumerate() {
+                    key.push(i.to_string());
+                    annotate(table, key, loaded, found);
+                    key.pop();
+                }
+            }
+            Item::Value(value) => {
+                let origin = loaded.origin(key);
+                value.decor_mut().set_suffix(format!(" # {origin}"));
+                found.insert(key.join("."), origin);
+            }
+            Item::None => {}
+        }
+
+        key.pop();
+    }
+}
@@ -0,0 +1,50 @@
+//! The `mksite explain` subcommand.
+
+use std::{
+    env,
+    path::{self, Component, PathBuf},
+};
+
+use crate::{cli, site, Error, Result};
+
+/// Arguments to the `mksite explain` subcommand.
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// A source page (eg `src/index.md`), static asset, or output file (eg
+    /// `out/index.html`).
+    pub(crate) path: PathBuf,
+}
+
+/// Prints how the given file is built.
+pub(crate) fn cmd(args: Args, project: &cli::Project) -> Result<()> {
+    // loading the config changes to the project directory, so make the path
+    // absolute first, then relative to the project directory afterwards
+    let path = path::absolute(&args.path).map_err(|source| Error::Io {
+        msg: format!("Cannot resolve '{}'", args.path.display()),
+        source,
+    })?;
+
+    // resolve `..` without touching the file system, like a shell would
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+
+    let config = project.load()?;
+
+    let cwd = env::current_dir().map_err(|source| Error::Io {
+        msg: "Cannot get the current directory".into(),
+        source,
+    })?;
+    let path = normalized.strip_prefix(&cwd).unwrap_or(&normalized);
+
+    let explanation = site::Site::new(config, site::Options::default())?.explain(path)?;
+
+    cli::print(&explanation)
+}
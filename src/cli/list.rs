@@ -0,0 +1,86 @@
+//! The `mksite list` subcommand.
+
+use std::fmt::Write as _;
+
+use crate::{
+    cli::{self, build},
+    site, Result,
+};
+
+/// Arguments to the `mksite list` subcommand.
+#[derive(clap::Args)]
+pub(crate) struct Args {
+    /// Print the routes as a JSON array instead of a table.
+    #[arg(long)]
+    pub(crate) json: bool,
+
+    /// Which pages to list.
+    #[command(flatten)]
+    pub(crate) pages: build::Pages,
+}
+
+/// Prints every file a build would write, along with its source, transform,
+/// and layout.
+pub(crate) fn cmd(args: Args, project: &cli::Project) -> Result<()> {
+    let config = project.load()?;
+    let options = site::Options {
+        dry_run: true,
+        ..args.pages.options()
+    };
+
+    let routes = site::Site::new(config, options)?.routes()?;
+
+    let output = if args.json {
+        serde_json::to_string_pretty(&routes).expect("Serializing routes should be infallible")
+            + "\n"
+    } else {
+        table(&routes)
+    };
+
+    cli::print(&output)
+}
+
+/// Formats routes as a table with a column each for the source, destination,
+/// transform, and layout.
+fn table(routes: &[site::Route]) -> String {
+    let rows = routes
+        .iter()
+        .map(|route| {
+            [
+                route.source.display().to_string(),
+                route.destination.display().to_string(),
+                route
+                    .transform
+                    .as_ref()
+                    .map_or("-".into(), ToString::to_string),
+                route
+                    .layout
+                    .as_ref()
+                    .map_or("-".into(), |layout| layout.display().to_string()),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let header = ["SOURCE", "DESTINATION", "TRANSFORM", "LAYOUT"].map(str::to_owned);
+
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut res = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+
+        let _ = writeln!(res, "{}", line.trim_end());
+    }
+
+    res
+}
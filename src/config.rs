@@ -2,8 +2,8 @@
 
 use std::{
     collections::HashMap,
-    fs,
-    io::Write,
+    env, fs,
+    io::{self, Write},
     path::{self, PathBuf},
 };
 
@@ -11,12 +11,19 @@ use crate::{Error, Result};
 
 use crate::transform;
 
+mod include;
+mod validate;
+
 /// The name of the config file to use.
 pub(crate) const FILE_NAME: &str = "mksite.toml";
 
+/// The lists that [merge] concatenates, rather than replacing.
+const APPENDED: &[&str] = &["ignores.template", "ignores.transform", "ignores.layout"];
+
 /// The configuration for a `mksite` project.
-#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 #[serde(default)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct Config {
     /// The list of important directories.
     #[serde(default)]
@@ -29,16 +36,82 @@ pub(crate) struct Config {
 
     /// Data to be passed to template rendering.
     #[serde(default)]
+    #[schemars(with = "HashMap<String, serde_json::Value>")]
     pub(crate) data: HashMap<String, toml::Value>,
 
     /// The list of transforms to apply, stored as a map of input formats to
     /// sub-maps of output formats and transforms.
     #[serde(default)]
     pub(crate) transforms: HashMap<String, HashMap<String, transform::Transform>>,
+
+    /// Settings for fingerprinting static assets.
+    #[serde(default)]
+    pub(crate) fingerprint: Fingerprint,
+
+    /// Settings for resizing and converting images.
+    #[serde(default)]
+    pub(crate) images: Images,
+
+    /// The absolute url the site is deployed at (eg `https://example.com`),
+    /// used wherever full urls are needed, such as in the sitemap.
+    #[serde(default)]
+    pub(crate) base_url: Option<String>,
+
+    /// Settings for sitemap generation.
+    #[serde(default)]
+    pub(crate) sitemap: Sitemap,
+
+    /// The list of RSS and Atom feeds to generate.
+    #[serde(default)]
+    pub(crate) feeds: Vec<Feed>,
+
+    /// Settings for client-side search index generation.
+    #[serde(default)]
+    pub(crate) search: Search,
+
+    /// Settings for checking links.
+    #[serde(default)]
+    pub(crate) links: Links,
+
+    /// Settings for writing pages as `index.html` files in their own
+    /// directories.
+    #[serde(default)]
+    pub(crate) pretty_urls: PrettyUrls,
+
+    /// Permalink patterns, stored as a map of globs matching _source_ pages to
+    /// patterns for the paths they are written to (eg
+    /// `"/blog/{year}/{month}/{slug}/"`). If several globs match a page, the
+    /// longest one wins.
+    #[serde(default)]
+    pub(crate) permalinks: HashMap<String, String>,
+
+    /// Redirects from old paths to new ones.
+    #[serde(default)]
+    pub(crate) redirects: Redirects,
+
+    /// Settings for reading environment variables in templates.
+    #[serde(default)]
+    pub(crate) env: Env,
+
+    /// Settings for sources that are written to the same path.
+    #[serde(default)]
+    pub(crate) collisions: Collisions,
+
+    /// Settings for deleting stale files from the out directory.
+    #[serde(default)]
+    pub(crate) prune: Prune,
+
+    /// The name of the profile that was applied when loading the config, if
+    /// any. Profiles are defined in `[profile.<name>]` tables, which are merged
+    /// into the rest of the config by [load], so this is not read from the
+    /// config file.
+    #[serde(skip)]
+    pub(crate) profile: Option<String>,
 }
 
 /// The names of all the important directories needed to build a site.
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct Dirs {
     /// The src directory holds template files to be rendered, transformed, and
     /// inserted into layouts.
@@ -70,7 +143,8 @@ pub(crate) struct Dirs {
 // TODO: document in readme
 /// The paths to files to be ignored during the templating, transform, and
 /// layout steps.
-#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct Ignores {
     /// Paths to source pages (eg `src/index.html`) to be ignored during
     /// templating. Pages ignored this way will not be passed through Tera, and
@@ -93,6 +167,388 @@ pub(crate) struct Ignores {
     pub(crate) layout: Vec<PathBuf>,
 }
 
+/// Settings for copying static assets to content-hashed filenames, so they can
+/// be served with long-lived cache headers.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Fingerprint {
+    /// Whether to fingerprint static assets. When disabled, the `asset_url`
+    /// template function still works, returning `prefix` followed by the
+    /// asset's own path.
+    #[serde(default)]
+    pub(crate) enable: bool,
+
+    /// The path of the JSON manifest mapping logical asset paths to
+    /// fingerprinted ones, relative to the out directory.
+    ///
+    /// The `serde` default is provided by the function [`Fingerprint::default_manifest`](Fingerprint::default_manifest).
+    #[serde(default = "Fingerprint::default_manifest")]
+    pub(crate) manifest: PathBuf,
+
+    /// The string prepended to asset paths by the `asset_url` template
+    /// function, eg `/` or `https://cdn.example.com/`.
+    ///
+    /// The `serde` default is provided by the function [`Fingerprint::default_prefix`](Fingerprint::default_prefix).
+    #[serde(default = "Fingerprint::default_prefix")]
+    pub(crate) prefix: String,
+
+    /// Globs matching the assets to fingerprint, relative to the static
+    /// directory. Other assets, such as `robots.txt` or fonts referenced from
+    /// stylesheets, keep their names.
+    ///
+    /// The `serde` default is provided by the function [`Fingerprint::default_include`](Fingerprint::default_include).
+    #[serde(default = "Fingerprint::default_include")]
+    pub(crate) include: Vec<String>,
+
+    /// Globs matching assets not to fingerprint, even if they match
+    /// `include`, relative to the static directory.
+    #[serde(default)]
+    pub(crate) exclude: Vec<String>,
+}
+
+/// Settings for the images produced by the `resize_image` and `srcset`
+/// template functions.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Images {
+    /// The directory processed images are written to, relative to the out
+    /// directory.
+    ///
+    /// The `serde` default is provided by the function [`Images::default_dir`](Images::default_dir).
+    #[serde(default = "Images::default_dir")]
+    pub(crate) dir: PathBuf,
+
+    /// The string prepended to the paths of processed images, eg `/`.
+    ///
+    /// The `serde` default is provided by the function [`Images::default_prefix`](Images::default_prefix).
+    #[serde(default = "Images::default_prefix")]
+    pub(crate) prefix: String,
+
+    /// The quality (1-100) to encode JPEG images with. WebP images are always
+    /// encoded losslessly.
+    ///
+    /// The `serde` default is provided by the function [`Images::default_quality`](Images::default_quality).
+    #[serde(default = "Images::default_quality")]
+    pub(crate) quality: u8,
+}
+
+/// Settings for generating a `sitemap.xml` file from the site's html pages.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Sitemap {
+    /// Whether to generate a sitemap. Requires `base_url` to be set.
+    #[serde(default)]
+    pub(crate) enable: bool,
+
+    /// The path of the sitemap, relative to the out directory.
+    ///
+    /// The `serde` default is provided by the function [`Sitemap::default_path`](Sitemap::default_path).
+    #[serde(default = "Sitemap::default_path")]
+    pub(crate) path: PathBuf,
+}
+
+/// A feed of pages to generate, eg for a blog.
+///
+/// ## Example
+/// ```toml
+/// [[feeds]]
+/// title = "My Blog"
+/// source = "src/blog/*.md"
+/// path = "blog/feed.xml"
+/// ```
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Feed {
+    /// The title of the feed.
+    pub(crate) title: String,
+
+    /// A description of the feed. Required by RSS, but may be left empty.
+    #[serde(default)]
+    pub(crate) description: String,
+
+    /// The author of the feed, used by Atom.
+    #[serde(default)]
+    pub(crate) author: Option<String>,
+
+    /// A glob or directory matching the _source_ pages (eg `src/blog/*.md`) to
+    /// include in the feed. Only html outputs of these pages are included.
+    pub(crate) source: String,
+
+    /// The path of the feed, relative to the out directory.
+    pub(crate) path: PathBuf,
+
+    /// The maximum number of pages to include, newest first.
+    ///
+    /// The `serde` default is provided by the function [`Feed::default_items`](Feed::default_items).
+    #[serde(default = "Feed::default_items")]
+    pub(crate) items: usize,
+
+    /// Whether to generate an Atom or RSS 2.0 feed.
+    #[serde(default)]
+    pub(crate) format: FeedFormat,
+
+    /// Whether to include each page's full content, or only a summary.
+    #[serde(default)]
+    pub(crate) content: FeedContent,
+}
+
+/// The format of a [Feed].
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    serde::Deserialize,
+    serde::Serialize,
+    schemars::JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FeedFormat {
+    /// An Atom feed.
+    #[default]
+    Atom,
+
+    /// An RSS 2.0 feed.
+    Rss,
+}
+
+/// How much of each page to include in a [Feed].
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    serde::Deserialize,
+    serde::Serialize,
+    schemars::JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FeedContent {
+    /// The full content of each page, after transforms but before layouts.
+    #[default]
+    Full,
+
+    /// The content before a `<!-- more -->` comment, or the first paragraph
+    /// if there is no such comment.
+    Summary,
+}
+
+/// Settings for generating a full-text search index of the site's html pages,
+/// along with a small script for querying it in the browser.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Search {
+    /// Whether to generate a search index.
+    #[serde(default)]
+    pub(crate) enable: bool,
+
+    /// The path of the JSON search index, relative to the out directory.
+    ///
+    /// The `serde` default is provided by the function [`Search::default_path`](Search::default_path).
+    #[serde(default = "Search::default_path")]
+    pub(crate) path: PathBuf,
+
+    /// The path of the search script, relative to the out directory.
+    ///
+    /// The `serde` default is provided by the function [`Search::default_script`](Search::default_script).
+    #[serde(default = "Search::default_script")]
+    pub(crate) script: PathBuf,
+
+    /// Globs matching _output_ pages (eg `out/docs/**`) to index. If empty,
+    /// every html page is indexed.
+    #[serde(default)]
+    pub(crate) include: Vec<String>,
+
+    /// Globs matching _output_ pages (eg `out/404.html`) to leave out of the
+    /// index.
+    #[serde(default)]
+    pub(crate) exclude: Vec<String>,
+}
+
+/// Settings for `mksite check`, mostly concerning links to other sites.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Links {
+    /// Whether to check links to other sites as well as internal links.
+    #[serde(default)]
+    pub(crate) external: bool,
+
+    /// The maximum number of external links to check at once.
+    ///
+    /// The `serde` default is provided by the function [`Links::default_concurrency`](Links::default_concurrency).
+    #[serde(default = "Links::default_concurrency")]
+    pub(crate) concurrency: usize,
+
+    /// How many times to retry an external link after a network error, a
+    /// server error, or a rate limit response.
+    ///
+    /// The `serde` default is provided by the function [`Links::default_retries`](Links::default_retries).
+    #[serde(default = "Links::default_retries")]
+    pub(crate) retries: u32,
+
+    /// How long to wait for a response, in seconds.
+    ///
+    /// The `serde` default is provided by the function [`Links::default_timeout`](Links::default_timeout).
+    #[serde(default = "Links::default_timeout")]
+    pub(crate) timeout: u64,
+
+    /// Globs matching the external urls to check (eg `https://docs.rs/*`). If
+    /// empty, every external url is checked.
+    #[serde(default)]
+    pub(crate) allow: Vec<String>,
+
+    /// Globs matching external urls to never check.
+    #[serde(default)]
+    pub(crate) deny: Vec<String>,
+
+    /// Where to remember the results of checking external links, relative to
+    /// the project root.
+    ///
+    /// The `serde` default is provided by the function [`Links::default_cache`](Links::default_cache).
+    #[serde(default = "Links::default_cache")]
+    pub(crate) cache: PathBuf,
+
+    /// How long to remember the result of checking an external link, in
+    /// hours.
+    ///
+    /// The `serde` default is provided by the function [`Links::default_max_age`](Links::default_max_age).
+    #[serde(default = "Links::default_max_age")]
+    pub(crate) max_age: u64,
+
+    /// An http proxy to send all requests through, eg `http://localhost:3128`.
+    #[serde(default)]
+    pub(crate) proxy: Option<String>,
+
+    /// Url prefixes to replace before checking, eg to check links to
+    /// `https://example.com` against `http://localhost:8000` instead.
+    #[serde(default)]
+    pub(crate) rewrite: HashMap<String, String>,
+}
+
+/// Settings for pretty urls, which write `src/about.md` to
+/// `out/about/index.html` rather than `out/about.html`, so it can be served at
+/// `/about/`. Only html pages are affected.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct PrettyUrls {
+    /// Whether to use pretty urls for every page.
+    #[serde(default)]
+    pub(crate) enable: bool,
+
+    /// Globs or directories matching _source_ pages (eg `src/blog`) to use
+    /// pretty urls for, even if they are not enabled for every page.
+    #[serde(default)]
+    pub(crate) include: Vec<String>,
+
+    /// Globs or directories matching _source_ pages (eg `src/404.md`) to never
+    /// use pretty urls for.
+    #[serde(default)]
+    pub(crate) exclude: Vec<String>,
+}
+
+/// Settings for redirecting old paths to new ones, eg after moving pages
+/// around.
+///
+/// ## Example
+/// ```toml
+/// [redirects]
+/// servers = ["netlify"]
+/// paths."/old/about.html" = "/about/"
+/// ```
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Redirects {
+    /// A map of old root-relative paths to the urls they now live at.
+    #[serde(default)]
+    pub(crate) paths: HashMap<String, String>,
+
+    /// Whether to write an html page with a meta refresh at each old path.
+    ///
+    /// The `serde` default is provided by the function [`Redirects::default_stubs`](Redirects::default_stubs).
+    #[serde(default = "Redirects::default_stubs")]
+    pub(crate) stubs: bool,
+
+    /// The web servers to write redirect configuration files for.
+    #[serde(default)]
+    pub(crate) servers: Vec<Server>,
+}
+
+/// Settings for the `env` template function. Environment variables can also
+/// be used in the config file itself, as `${VAR}` or `${VAR:-default}`.
+///
+/// ## Example
+/// ```toml
+/// [env]
+/// allow = ["GIT_SHA", "DEPLOY_URL"]
+/// ```
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Env {
+    /// The names of the environment variables templates are allowed to read.
+    #[serde(default)]
+    pub(crate) allow: Vec<String>,
+}
+
+/// Settings for sources that are written to the same path, eg `src/index.md`
+/// and `src/index.html`, or a static asset with the same path as a page. These
+/// are errors unless `precedence` says which source wins.
+///
+/// ## Example
+/// ```toml
+/// [collisions]
+/// precedence = ["static", "src/**/*.md"]
+/// ```
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Collisions {
+    /// Globs or directories matching source pages or static assets, highest
+    /// precedence first. When several sources are written to the same path,
+    /// the one matching the earliest entry wins, provided no other source
+    /// matches that entry too.
+    #[serde(default)]
+    pub(crate) precedence: Vec<String>,
+}
+
+/// Settings for deleting files from the out directory that a build did not
+/// write, such as pages left behind after their source was renamed or deleted.
+///
+/// ## Example
+/// ```toml
+/// [prune]
+/// enable = true
+/// keep = [".git", "CNAME"]
+/// ```
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct Prune {
+    /// Whether to delete stale files after every build.
+    #[serde(default)]
+    pub(crate) enable: bool,
+
+    /// Globs or directories matching paths relative to the out directory (eg
+    /// `.git` or `CNAME`) to never delete.
+    #[serde(default)]
+    pub(crate) keep: Vec<String>,
+}
+
+/// A web server that [Redirects] can generate configuration for.
+#[derive(
+    Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Server {
+    /// A Netlify-style `_redirects` file, also understood by Cloudflare Pages.
+    Netlify,
+
+    /// An nginx `map` block, written to `redirects.nginx.conf`.
+    Nginx,
+
+    /// An Apache `.htaccess` file.
+    Apache,
+}
+
 impl Dirs {
     /// Returns the default 'src/' directory.
     fn default_src() -> PathBuf {
@@ -126,14 +582,480 @@ impl Default for Dirs {
     }
 }
 
-/// Loads the `mksite.toml` config file from the current directory.
-pub(crate) fn load() -> Result<Config> {
-    let config = fs::read_to_string(FILE_NAME).map_err(|source| Error::Io {
-        msg: format!("Cannot read {FILE_NAME}"),
+impl Fingerprint {
+    /// Returns the default manifest path, 'manifest.json'.
+    fn default_manifest() -> PathBuf {
+        "manifest.json".into()
+    }
+
+    /// Returns the default url prefix, '/'.
+    fn default_prefix() -> String {
+        "/".into()
+    }
+
+    /// Returns the default assets to fingerprint, stylesheets and scripts.
+    fn default_include() -> Vec<String> {
+        vec!["**/*.css".into(), "**/*.js".into()]
+    }
+}
+
+impl Default for Fingerprint {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            manifest: Self::default_manifest(),
+            prefix: Self::default_prefix(),
+            include: Self::default_include(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl Images {
+    /// Returns the default processed image directory, 'images/'.
+    fn default_dir() -> PathBuf {
+        "images".into()
+    }
+
+    /// Returns the default url prefix, '/'.
+    fn default_prefix() -> String {
+        "/".into()
+    }
+
+    /// Returns the default JPEG quality, 80.
+    fn default_quality() -> u8 {
+        80
+    }
+}
+
+impl Default for Images {
+    fn default() -> Self {
+        Self {
+            dir: Self::default_dir(),
+            prefix: Self::default_prefix(),
+            quality: Self::default_quality(),
+        }
+    }
+}
+
+impl Sitemap {
+    /// Returns the default sitemap path, 'sitemap.xml'.
+    fn default_path() -> PathBuf {
+        "sitemap.xml".into()
+    }
+}
+
+impl Default for Sitemap {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            path: Self::default_path(),
+        }
+    }
+}
+
+impl Feed {
+    /// Returns the default number of feed items, 20.
+    fn default_items() -> usize {
+        20
+    }
+}
+
+impl Search {
+    /// Returns the default search index path, 'search-index.json'.
+    fn default_path() -> PathBuf {
+        "search-index.json".into()
+    }
+
+    /// Returns the default search script path, 'search.js'.
+    fn default_script() -> PathBuf {
+        "search.js".into()
+    }
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            path: Self::default_path(),
+            script: Self::default_script(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl Links {
+    /// Returns the default number of concurrent requests, 8.
+    fn default_concurrency() -> usize {
+        8
+    }
+
+    /// Returns the default number of retries, 2.
+    fn default_retries() -> u32 {
+        2
+    }
+
+    /// Returns the default request timeout, 10 seconds.
+    fn default_timeout() -> u64 {
+        10
+    }
+
+    /// Returns the default link cache path, '.mksite-cache/links.json'.
+    fn default_cache() -> PathBuf {
+        ".mksite-cache/links.json".into()
+    }
+
+    /// Returns the default cache lifetime, 24 hours.
+    fn default_max_age() -> u64 {
+        24
+    }
+}
+
+impl Default for Links {
+    fn default() -> Self {
+        Self {
+            external: false,
+            concurrency: Self::default_concurrency(),
+            retries: Self::default_retries(),
+            timeout: Self::default_timeout(),
+            allow: Vec::new(),
+            deny: Vec::new(),
+            cache: Self::default_cache(),
+            max_age: Self::default_max_age(),
+            proxy: None,
+            rewrite: HashMap::new(),
+        }
+    }
+}
+
+impl Redirects {
+    /// Returns the default for writing html redirect pages, true.
+    fn default_stubs() -> bool {
+        true
+    }
+}
+
+impl Default for Redirects {
+    fn default() -> Self {
+        Self {
+            paths: HashMap::new(),
+            stubs: Self::default_stubs(),
+            servers: Vec::new(),
+        }
+    }
+}
+
+/// Returns the path to the config file: `file` if given, or else the first
+/// `mksite.toml` found in the current directory or one of its parents.
+pub(crate) fn find(file: Option<&path::Path>) -> Result<PathBuf> {
+    if let Some(file) = file {
+        return Ok(file.to_owned());
+    }
+
+    let cwd = env::current_dir().map_err(|source| Error::Io {
+        msg: "Cannot get the current directory".into(),
         source,
     })?;
 
-    toml::from_str(&config).map_err(|source| source.into())
+    cwd.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+        // keep paths in the current directory short, for messages
+        .map(|path| match path.strip_prefix(&cwd) {
+            Ok(relative) => relative.to_owned(),
+            Err(_) => path,
+        })
+        .ok_or_else(|| Error::Io {
+            msg: format!(
+                "Cannot find {FILE_NAME} in '{}' or any parent directory",
+                cwd.display()
+            ),
+            source: io::ErrorKind::NotFound.into(),
+        })
+}
+
+/// A config read by [read], along with the files it was read from and any
+/// problems found reading it.
+pub(crate) struct Loaded {
+    /// The config itself.
+    pub(crate) config: Config,
+
+    /// Every config file that was read, in the order they were merged.
+    pub(crate) files: Vec<include::File>,
+
+    /// Keys in the config files that don't match any setting, as descriptions
+    /// including their locations where possible (eg
+    /// `` `dirs.scr' at 'mksite.toml:3:1' ``).
+    pub(crate) unknown: Vec<String>,
+
+    /// The config files merged together, before any profile is applied or
+    /// environment variables are interpolated.
+    base: toml::Table,
+
+    /// Every profile defined in the config files, by name.
+    profiles: toml::Table,
+}
+
+impl Loaded {
+    /// Logs a warning for every unknown key.
+    pub(crate) fn warn_unknown(&self) {
+        for key in &self.unknown {
+            log::warn!("Unknown key {key}");
+        }
+    }
+
+    /// Returns the unknown keys in every profile other than the active one, as
+    /// descriptions like [Loaded::unknown]. Keys already in
+    /// [Loaded::unknown] are left out. Environment variables are not
+    /// interpolated, so profiles can be checked without their variables set.
+    pub(crate) fn unknown_in_profiles(&self) -> Result<Vec<String>> {
+        let mut res = Vec::new();
+
+        for (name, overrides) in &self.profiles {
+            if self.config.profile.as_deref() == Some(name) {
+                continue;
+            }
+
+            let toml::Value::Table(overrides) = overrides else {
+                return Err(Error::Config(format!("`profile.{name}' must be a table")));
+            };
+
+            let mut table = self.base.clone();
+            merge(&mut table, overrides.clone());
+
+            let mut unknown = Vec::new();
+            serde_ignored::deserialize::<_, _, Config>(table, |key| {
+                unknown.push(validate::segments(&key));
+            })
+            .map_err(|e| Error::Config(format!("`profile.{name}': {e}")))?;
+
+            for key in unknown {
+                // report keys set in the profile itself by their full path
+                let in_profile = ["profile", name]
+                    .into_iter()
+                    .map(str::to_owned)
+                    .chain(key.iter().cloned())
+                    .collect::<Vec<_>>();
+
+                let description = if validate::locate(&in_profile, &self.files, None).is_some() {
+                    validate::describe(&in_profile, &self.files, None)
+                } else {
+                    validate::describe(&key, &self.files, Some(name))
+                };
+
+                if !self.unknown.contains(&description) && !res.contains(&description) {
+                    res.push(description);
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Returns where the setting at the path `key` (eg `["dirs", "src"]`) got
+    /// its value: the file, line, and column it is set at, or `default` if it
+    /// isn't set in any config file.
+    pub(crate) fn origin(&self, key: &[String]) -> String {
+        validate::locate(key, &self.files, self.config.profile.as_deref())
+            .unwrap_or_else(|| "default".into())
+    }
+}
+
+/// Loads the config like [read], warning about unknown keys, and checks it
+/// with [Config::validate].
+pub(crate) fn load(file: Option<&path::Path>, profile: Option<&str>) -> Result<Config> {
+    let loaded = read(file, profile)?;
+
+    loaded.warn_unknown();
+    loaded.config.validate()?;
+
+    Ok(loaded.config)
+}
+
+/// Reads the config file found by [find], and changes the current directory to
+/// the one containing it, so that every path in the config is relative to the
+/// config file.
+///
+/// The config is built up in layers, each merged over the last as described in
+/// [merge]:
+///
+/// - The user-level defaults file, if it exists (see
+///   [include::user_defaults]).
+/// - The files included by the config file, and the config file itself (see
+///   [include::read]).
+/// - The `[profile.<name>]` table matching `profile`, if given.
+///
+/// Environment variables in string values are then interpolated, as described
+/// in [env::interpolate](crate::env::interpolate).
+pub(crate) fn read(file: Option<&path::Path>, profile: Option<&str>) -> Result<Loaded> {
+    let path = find(file)?;
+
+    let mut table = toml::Table::new();
+    let mut files = Vec::new();
+
+    if let Some(defaults) = include::user_defaults().filter(|p| p.is_file()) {
+        log::debug!("Using user defaults '{}'", defaults.display());
+        merge(&mut table, include::read(&defaults, &mut files)?);
+    }
+
+    merge(&mut table, include::read(&path, &mut files)?);
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if !dir
+            .canonicalize()
+            .is_ok_and(|dir| env::current_dir().is_ok_and(|cwd| cwd == dir))
+        {
+            log::info!("Using config file '{}'", path.display());
+        }
+
+        env::set_current_dir(dir).map_err(|source| Error::Io {
+            msg: format!("Cannot change directory to '{}'", dir.display()),
+            source,
+        })?;
+    }
+
+    let profiles = match table.remove("profile") {
+        None => toml::Table::new(),
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => return Err(Error::Config("`profile' must be a table".into())),
+    };
+
+    if let Some(name) = profile {
+        match profiles.get(name) {
+            Some(toml::Value::Table(_)) => log::info!("Using profile `{name}'"),
+            Some(_) => return Err(Error::Config(format!("`profile.{name}' must be a table"))),
+            None if profiles.is_empty() => {
+                return Err(Error::Config(format!(
+                    "No profile `{name}', as no profiles are defined in '{}'",
+                    path.display()
+                )))
+            }
+            None => {
+                return Err(Error::Config(format!(
+                    "No profile `{name}' (defined profiles: {})",
+                    profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+                )))
+            }
+        }
+    }
+
+    let base = table.clone();
+
+    if let Some(name) = profile {
+        if let Some(toml::Value::Table(overrides)) = profiles.get(name) {
+            merge(&mut table, overrides.clone());
+        }
+    }
+
+    crate::env::interpolate(&mut table)?;
+
+    let mut unknown = Vec::new();
+    let mut config: Config = serde_ignored::deserialize(table, |key| {
+        unknown.push(validate::segments(&key));
+    })?;
+    config.profile = profile.map(str::to_owned);
+
+    let unknown = unknown
+        .iter()
+        .map(|key| validate::describe(key, &files, profile))
+        .collect();
+
+    Ok(Loaded {
+        config,
+        files,
+        unknown,
+        base,
+        profiles,
+    })
+}
+
+/// Merges `overrides` into `base`. Tables (like `data` and `transforms`) are
+/// merged key by key, recursively, and the lists in `ignores` are concatenated.
+/// Any other value in `overrides`, including other arrays, replaces the value
+/// in `base`.
+pub(crate) fn merge(base: &mut toml::Table, overrides: toml::Table) {
+    merge_at(base, overrides, "");
+}
+
+/// Merges `overrides` into `base` like [merge], where `base` is found at the
+/// dotted key path `path`.
+fn merge_at(base: &mut toml::Table, overrides: toml::Table, path: &str) {
+    for (key, value) in overrides {
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge_at(base, overrides, &path)
+            }
+            (Some(toml::Value::Array(base)), toml::Value::Array(overrides))
+                if APPENDED.contains(&path.as_str()) =>
+            {
+                for value in overrides {
+                    if !base.contains(&value) {
+                        base.push(value);
+                    }
+                }
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Returns a JSON Schema describing the config file, for editors and linters.
+/// Unlike [load], the schema rejects unknown keys.
+pub(crate) fn schema() -> serde_json::Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(Config))
+        .expect("Serializing the schema should be infallible");
+
+    // these are handled before deserializing, so they aren't part of [Config]
+    schema["properties"]["include"] = serde_json::json!({
+        "description": "Other config files to merge beneath this one, relative to it. Globs are allowed.",
+        "type": "array",
+        "items": { "type": "string" }
+    });
+    schema["properties"]["profile"] = serde_json::json!({
+        "description": "Profiles, which override the rest of the config when selected with `--profile` or `MKSITE_ENV`.",
+        "type": "object",
+        "additionalProperties": { "$ref": "#" }
+    });
+
+    clean_descriptions(&mut schema);
+
+    schema
+}
+
+/// Removes the notes about `serde` defaults and the `## Example` sections from
+/// the doc comments used as descriptions in a schema, as they only make sense
+/// in the source code. `schemars` flattens examples into a single line, which
+/// editors show as a garbled paragraph.
+fn clean_descriptions(schema: &mut serde_json::Value) {
+    match schema {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match value {
+                    serde_json::Value::String(text) if key == "description" => {
+                        // examples come last, and may span several paragraphs
+                        *text = text
+                            .split("\n\n")
+                            .take_while(|para| !para.starts_with("## Example"))
+                            .filter(|para| !para.starts_with("The `serde` default is provided"))
+                            .collect::<Vec<_>>()
+                            .join("\n\n");
+                    }
+                    value => clean_descriptions(value),
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(clean_descriptions),
+        _ => {}
+    }
 }
 
 /// Generates the `mksite.toml` config file in the specified directory.
@@ -163,3 +1085,198 @@ pub(crate) fn generate(path: &path::Path) -> Result<()> {
             source,
         })
 }
+
+#[cfg(test)]
+mod tests {
+    //! Tests for merging config layers.
+
+    use super::*;
+
+    /// Parses `text` as a TOML table.
+    fn table(text: &str) -> toml::Table {
+        text.parse().unwrap()
+    }
+
+    /// Tables are merged key by key at every level, and scalars replace.
+    #[test]
+    fn tables() {
+        let mut base = table(
+            r#"
+            base_url = "https://example.com"
+            [dirs]
+            src = "src"
+            out = "out"
+            [data.site]
+            title = "Title"
+            author = "Me"
+            "#,
+        );
+
+        merge(
+            &mut base,
+            table(
+                r#"
+                base_url = "https://staging.example.com"
+                [dirs]
+                out = "public"
+                [data.site]
+                title = "Staging"
+                [data.extra]
+                flag = true
+                "#,
+            ),
+        );
+
+        assert_eq!(
+            base,
+            table(
+                r#"
+                base_url = "https://staging.example.com"
+                [dirs]
+                src = "src"
+                out = "public"
+                [data.site]
+                title = "Staging"
+                author = "Me"
+                [data.extra]
+                flag = true
+                "#,
+            )
+        );
+    }
+
+    /// The `ignores` lists are appended without duplicates, while other arrays
+    /// replace, and a value of a different type replaces a table.
+    #[test]
+    fn arrays() {
+        let mut base = table(
+            r#"
+            [ignores]
+            template = ["src/a.html", "src/b.html"]
+            [search]
+            exclude = ["out/a"]
+            [data]
+            list = [1, 2]
+            nested = { key = 1 }
+            "#,
+        );
+
+        merge(
+            &mut base,
+            table(
+                r#"
+                [ignores]
+                template = ["src/b.html", "src/c.html"]
+                layout = ["out/x.html"]
+                [search]
+                exclude = ["out/b"]
+                [data]
+                list = [3]
+                nested = "replaced"
+                "#,
+            ),
+        );
+
+        assert_eq!(
+            base,
+            table(
+                r#"
+                [ignores]
+                template = ["src/a.html", "src/b.html", "src/c.html"]
+                layout = ["out/x.html"]
+                [search]
+                exclude = ["out/b"]
+                [data]
+                list = [3]
+                nested = "replaced"
+                "#,
+            )
+        );
+    }
+
+    /// Included files are merged beneath the file including them, in order,
+    /// with the same semantics.
+    #[test]
+    fn includes() {
+        let dir =
+            std::env::temp_dir().join(format!("mksite-config-includes-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(
+            dir.join("first.toml"),
+            "[data]\na = 1\nb = 1\n[ignores]\ntemplate = [\"src/first.html\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("second.toml"),
+            "[data]\nb = 2\nc = 2\n[ignores]\ntemplate = [\"src/second.html\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join(FILE_NAME),
+            "include = [\"first.toml\", \"second.toml\"]\n[data]\nc = 3\n[ignores]\ntemplate = [\"src/first.html\", \"src/main.html\"]\n",
+        )
+        .unwrap();
+
+        let mut files = Vec::new();
+        let merged = include::read(&dir.join(FILE_NAME), &mut files).unwrap();
+
+        assert_eq!(
+            merged,
+            table(
+                r#"
+                [data]
+                a = 1
+                b = 2
+                c = 3
+                [ignores]
+                template = ["src/first.html", "src/second.html", "src/main.html"]
+                "#,
+            )
+        );
+        assert_eq!(files.len(), 3);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// A profile is merged over the rest of the config like any other layer.
+    #[test]
+    fn profiles() {
+        let mut base = table(
+            r#"
+            [data]
+            analytics = false
+            name = "Site"
+            [ignores]
+            transform = ["out/raw.html"]
+            [profile.production.data]
+            analytics = true
+            [profile.production.ignores]
+            transform = ["out/draft.html"]
+            "#,
+        );
+
+        let Some(toml::Value::Table(mut profiles)) = base.remove("profile") else {
+            panic!("`profile' should be a table");
+        };
+        let Some(toml::Value::Table(production)) = profiles.remove("production") else {
+            panic!("`profile.production' should be a table");
+        };
+
+        merge(&mut base, production);
+
+        assert_eq!(
+            base,
+            table(
+                r#"
+                [data]
+                analytics = true
+                name = "Site"
+                [ignores]
+                transform = ["out/raw.html", "out/draft.html"]
+                "#,
+            )
+        );
+    }
+}
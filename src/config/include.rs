@@ -0,0 +1,152 @@
+//! Reading config files along with the files they include.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{util, Error, Result};
+
+/// Characters that make an include path a glob pattern.
+const GLOB_CHARS: &[char] = &['*', '?', '[', '{'];
+
+/// Returns the path of the user-level defaults file, which is layered beneath
+/// every project's config: `$XDG_CONFIG_HOME/mksite/defaults.toml`, or
+/// `~/.config/mksite/defaults.toml` if `XDG_CONFIG_HOME` is not set.
+pub(crate) fn user_defaults() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(dir.join("mksite").join("defaults.toml"))
+}
+
+/// A config file read while loading the config.
+pub(crate) struct File {
+    /// The path to the file.
+    pub(crate) path: PathBuf,
+
+    /// The contents of the file.
+    pub(crate) contents: String,
+}
+
+/// Reads the config file at `path` into a table, with the files listed in its
+/// `include` array merged beneath it, in order. Include paths are relative to
+/// the file that includes them, and may be globs, whose matches are included
+/// in sorted order. Included files may include other files in turn.
+///
+/// Every file read is added to `files`, in the order they are merged.
+pub(crate) fn read(path: &Path, files: &mut Vec<File>) -> Result<toml::Table> {
+    read_nested(path, files, &mut Vec::new())
+}
+
+/// Reads the config file at `path` like [read]. `stack` holds the files
+/// currently being read, to catch files that (indirectly) include themselves.
+fn read_nested(
+    path: &Path,
+    files: &mut Vec<File>,
+    stack: &mut Vec<PathBuf>,
+) -> Result<toml::Table> {
+    let canonical = path.canonicalize().map_err(|source| Error::Io {
+        msg: format!("Cannot read '{}'", path.display()),
+        source,
+    })?;
+
+    if stack.contains(&canonical) {
+        return Err(Error::Config(format!(
+            "'{}' includes itself",
+            path.display()
+        )));
+    }
+
+    let contents = fs::read_to_string(path).map_err(|source| Error::Io {
+        msg: format!("Cannot read '{}'", path.display()),
+        source,
+    })?;
+
+    let mut table = toml::from_str::<toml::Table>(&contents)
+        .map_err(|e| Error::Config(format!("Cannot parse '{}': {e}", path.display())))?;
+
+    let includes = match table.remove("include") {
+        None => Vec::new(),
+        Some(toml::Value::Array(includes)) => includes
+            .into_iter()
+            .map(|include| match include {
+                toml::Value::String(include) => Ok(include),
+                _ => Err(Error::Config(format!(
+                    "`include' in '{}' must be an array of strings",
+                    path.display()
+                ))),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Some(_) => {
+            return Err(Error::Config(format!(
+                "`include' in '{}' must be an array of strings",
+                path.display()
+            )))
+        }
+    };
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut res = toml::Table::new();
+
+    stack.push(canonical);
+
+    for include in includes {
+        for file in expand(dir, &include)? {
+            log::debug!("Including '{}' from '{}'", file.display(), path.display());
+
+            super::merge(&mut res, read_nested(&file, files, stack)?);
+        }
+    }
+
+    stack.pop();
+
+    super::merge(&mut res, table);
+
+    files.push(File {
+        path: path.to_owned(),
+        contents,
+    });
+
+    Ok(res)
+}
+
+/// Returns the files an include path relative to `dir` refers to. Plain paths
+/// refer to exactly one file, which must exist, while globs can match any
+/// number of files.
+fn expand(dir: &Path, include: &str) -> Result<Vec<PathBuf>> {
+    if !include.contains(GLOB_CHARS) {
+        return Ok(vec![dir.join(include)]);
+    }
+
+    let glob = globset::GlobBuilder::new(include)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| Error::Config(format!("Invalid include `{include}': {e}")))?
+        .compile_matcher();
+
+    // only walk the part of the tree the glob can match
+    let base = Path::new(include)
+        .components()
+        .take_while(|part| !part.as_os_str().to_string_lossy().contains(GLOB_CHARS))
+        .collect::<PathBuf>();
+    let base = dir.join(base);
+
+    if !base.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut res = Vec::new();
+
+    for file in util::walk_dir(&base)? {
+        if glob.is_match(util::swap_prefix(&file, dir, "")?) {
+            res.push(file);
+        }
+    }
+
+    res.sort();
+
+    Ok(res)
+}
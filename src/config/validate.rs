@@ -0,0 +1,181 @@
+//! Checking the config for mistakes that deserializing it doesn't catch.
+
+use std::path::{Component, Path, PathBuf};
+
+use toml_edit::{Item, TableLike, Value};
+
+use super::{include::File, Config};
+use crate::{redirect, transform::Transform, Error, Result};
+
+impl Config {
+    /// Checks that none of the `dirs` overlap, that every transform command can
+    /// be parsed, that no redirect leads outside the out directory, and that
+    /// `images.quality` is in range.
+    pub(crate) fn validate(&self) -> Result<()> {
+        let dirs = [
+            ("src", normalize(&self.dirs.src)),
+            ("out", normalize(&self.dirs.out)),
+            ("static", normalize(&self.dirs.r#static)),
+            ("layout", normalize(&self.dirs.layout)),
+        ];
+
+        for (i, (a, a_path)) in dirs.iter().enumerate() {
+            for (b, b_path) in &dirs[i + 1..] {
+                if a_path.starts_with(b_path) || b_path.starts_with(a_path) {
+                    return Err(Error::Config(format!(
+                        "`dirs.{a}' ('{}') and `dirs.{b}' ('{}') overlap",
+                        a_path.display(),
+                        b_path.display()
+                    )));
+                }
+            }
+        }
+
+        for (from, targets) in &self.transforms {
+            for (to, transform) in targets {
+                let commands = match transform {
+                    Transform::Single(command) => vec![command],
+                    Transform::Chain(commands) => commands.iter().collect(),
+                };
+
+                if commands.is_empty() {
+                    return Err(Error::Config(format!(
+                        "Transform `{from}.{to}' has no commands"
+                    )));
+                }
+
+                for command in commands {
+                    let argv = shell_words::split(command).map_err(|source| Error::Shell {
+                        command: command.clone(),
+                        source,
+                    })?;
+
+                    if argv.is_empty() {
+                        return Err(Error::Config(format!(
+                            "Transform `{from}.{to}' has an empty command"
+                        )));
+                    }
+                }
+            }
+        }
+
+        for from in self.redirects.paths.keys() {
+            redirect::stub_path(from, &self.dirs.out)?;
+        }
+
+        if !(1..=100).contains(&self.images.quality) {
+            return Err(Error::Config(format!(
+                "`images.quality' must be from 1 to 100, not {}",
+                self.images.quality
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes `.` components from a path, so that `./out` and `out` compare
+/// equal.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|part| *part != Component::CurDir)
+        .collect()
+}
+
+/// Returns the segments of a key path reported by `serde_ignored` (eg
+/// `["feeds", "0", "titel"]`).
+pub(crate) fn segments(key: &serde_ignored::Path) -> Vec<String> {
+    let mut res = match key {
+        serde_ignored::Path::Root => return Vec::new(),
+        serde_ignored::Path::Seq { parent, .. }
+        | serde_ignored::Path::Map { parent, .. }
+        | serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => segments(parent),
+    };
+
+    match key {
+        serde_ignored::Path::Seq { index, .. } => res.push(index.to_string()),
+        serde_ignored::Path::Map { key, .. } => res.push(key.clone()),
+        _ => {}
+    }
+
+    res
+}
+
+/// Describes an unknown key for a warning, including where it was found (see
+/// [locate]).
+pub(crate) fn describe(key: &[String], files: &[File], profile: Option<&str>) -> String {
+    let dotted = key.join(".");
+
+    match locate(key, files, profile) {
+        Some(location) => format!("`{dotted}' at '{location}'"),
+        None => format!("`{dotted}'"),
+    }
+}
+
+/// Returns the file, line, and column the key at the path `key` is set at (eg
+/// `mksite.toml:3:1`), if it is set in any of `files`. Later files are
+/// searched first, as they take precedence, as is the active `profile`'s
+/// table.
+pub(crate) fn locate(key: &[String], files: &[File], profile: Option<&str>) -> Option<String> {
+    let mut candidates = Vec::new();
+    if let Some(profile) = profile {
+        candidates.push(
+            ["profile", profile]
+                .into_iter()
+                .map(str::to_owned)
+                .chain(key.iter().cloned())
+                .collect::<Vec<_>>(),
+        );
+    }
+    candidates.push(key.to_vec());
+
+    for file in files.iter().rev() {
+        let Ok(document) = toml_edit::ImDocument::parse(file.contents.as_str()) else {
+            continue;
+        };
+
+        for candidate in &candidates {
+            if let Some(offset) = key_offset(document.as_table(), candidate) {
+                let before = &file.contents[..offset];
+                let line = before.matches('\n').count() + 1;
+                let column = before
+                    .rsplit('\n')
+                    .next()
+                    .unwrap_or_default()
+                    .chars()
+                    .count()
+                    + 1;
+
+                return Some(format!("{}:{line}:{column}", file.path.display()));
+            }
+        }
+    }
+
+    None
+}
+
+/// Returns the byte offset of the key at the path `key` in `table`, if it is
+/// there.
+fn key_offset(table: &dyn TableLike, key: &[String]) -> Option<usize> {
+    let (first, rest) = key.split_first()?;
+    let (found, item) = table.get_key_value(first)?;
+
+    if rest.is_empty() {
+        return found.span().map(|span| span.start);
+    }
+
+    // step into arrays of tables (eg `feeds.0.title`)
+    match item {
+        Item::ArrayOfTables(tables) => {
+            let (index, rest) = rest.split_first()?;
+            key_offset(tables.get(index.parse().ok()?)?, rest)
+        }
+        Item::Value(Value::Array(values)) => {
+            let (index, rest) = rest.split_first()?;
+            key_offset(values.get(index.parse().ok()?)?.as_inline_table()?, rest)
+        }
+        item => key_offset(item.as_table_like()?, rest),
+    }
+}
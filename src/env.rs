@@ -0,0 +1,177 @@
+//! Environment variable interpolation in the config file, and the `env`
+//! template function.
+
+use std::{collections::HashMap, env};
+
+use crate::{Error, Result};
+
+/// Replaces every `${VAR}` in the string values of `table` (including those in
+/// nested tables and arrays) with the value of the environment variable `VAR`,
+/// and every `${VAR:-default}` with `default` if `VAR` is unset or empty. `$${`
+/// is left as a literal `${`.
+pub(crate) fn interpolate(table: &mut toml::Table) -> Result<()> {
+    for (key, value) in table.iter_mut() {
+        interpolate_value(value, key)?;
+    }
+
+    Ok(())
+}
+
+/// Interpolates the strings in `value`, which is found at the dotted key path
+/// `key`.
+fn interpolate_value(value: &mut toml::Value, key: &str) -> Result<()> {
+    match value {
+        toml::Value::String(text) => {
+            *text = interpolate_str(text).map_err(|e| Error::Config(format!("`{key}': {e}")))?;
+        }
+        toml::Value::Array(values) => {
+            for (i, value) in values.iter_mut().enumerate() {
+                interpolate_value(value, &format!("{key}[{i}]"))?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (k, value) in table.iter_mut() {
+                interpolate_value(value, &format!("{key}.{k}"))?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Interpolates environment variables in a single string, returning a
+/// description of the problem if a variable is unset or a `${` is unclosed.
+fn interpolate_str(text: &str) -> std::result::Result<String, String> {
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            res.push_str(&rest[..start - 1]);
+            res.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        res.push_str(&rest[..start]);
+
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed `${{' in `{text}'"))?;
+        let inner = &rest[start + 2..start + end];
+
+        let value = match inner.split_once(":-") {
+            Some((name, default)) => env::var(name)
+                .ok()
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| default.to_owned()),
+            None => {
+                env::var(inner).map_err(|_| format!("environment variable `{inner}' is not set"))?
+            }
+        };
+
+        res.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+
+    res.push_str(rest);
+
+    Ok(res)
+}
+
+/// Returns the `env` template function, which reads an environment variable.
+/// Only the variables in `allow` can be read, so that templates can't leak
+/// secrets from the environment into the site. If the variable is unset, the
+/// optional `default` argument is returned instead.
+///
+/// ## Example
+/// ```html
+/// <meta name="revision" content="{{ env(name="GIT_SHA", default="dev") }}" />
+/// ```
+pub(crate) fn function(allow: Vec<String>) -> impl tera::Function {
+    move |args: &HashMap<String, tera::Value>| {
+        let name = args
+            .get("name")
+            .and_then(tera::Value::as_str)
+            .ok_or_else(|| tera::Error::msg("`env` requires a string `name` argument"))?;
+
+        if !allow.iter().any(|allowed| allowed == name) {
+            return Err(tera::Error::msg(format!(
+                "`env`: environment variable `{name}' is not in `env.allow'"
+            )));
+        }
+
+        match (env::var(name), args.get("default")) {
+            (Ok(value), _) => Ok(tera::Value::String(value)),
+            (Err(_), Some(default)) => Ok(default.clone()),
+            (Err(_), None) => Err(tera::Error::msg(format!(
+                "`env`: environment variable `{name}' is not set"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! Tests for interpolating environment variables.
+
+    use super::*;
+
+    /// `$${` escapes interpolation and is left as a literal `${`.
+    #[test]
+    fn escaped() {
+        assert_eq!(
+            interpolate_str("$${MKSITE_TEST_ESCAPED} $${").as_deref(),
+            Ok("${MKSITE_TEST_ESCAPED} ${")
+        );
+    }
+
+    /// Set variables are substituted, and unset ones are an error.
+    #[test]
+    fn set_and_unset() {
+        env::set_var("MKSITE_TEST_SET", "value");
+
+        assert_eq!(
+            interpolate_str("a ${MKSITE_TEST_SET} b").as_deref(),
+            Ok("a value b")
+        );
+        assert!(interpolate_str("${MKSITE_TEST_UNSET}")
+            .unwrap_err()
+            .contains("`MKSITE_TEST_UNSET' is not set"));
+    }
+
+    /// `${VAR:-default}` falls back to the default if the variable is unset or
+    /// empty, and the default may itself be empty.
+    #[test]
+    fn defaults() {
+        env::set_var("MKSITE_TEST_EMPTY", "");
+        env::set_var("MKSITE_TEST_DEFAULTED", "value");
+
+        assert_eq!(
+            interpolate_str("${MKSITE_TEST_EMPTY:-fallback}").as_deref(),
+            Ok("fallback")
+        );
+        assert_eq!(
+            interpolate_str("[${MKSITE_TEST_EMPTY:-}]").as_deref(),
+            Ok("[]")
+        );
+        assert_eq!(
+            interpolate_str("[${MKSITE_TEST_UNSET_DEFAULT:-}]").as_deref(),
+            Ok("[]")
+        );
+        assert_eq!(
+            interpolate_str("${MKSITE_TEST_DEFAULTED:-fallback}").as_deref(),
+            Ok("value")
+        );
+    }
+
+    /// An unclosed `${` is an error rather than being passed through.
+    #[test]
+    fn unclosed() {
+        assert!(interpolate_str("a ${MKSITE_TEST_SET b")
+            .unwrap_err()
+            .contains("unclosed"));
+        assert!(interpolate_str("${").unwrap_err().contains("unclosed"));
+    }
+}
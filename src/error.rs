@@ -28,6 +28,31 @@ pub(crate) enum Error {
     #[error(transparent)]
     Log(#[from] log::SetLoggerError),
 
+    /// The config file is well-formed, but its contents are not valid.
+    #[error("Invalid {}: {0}", crate::config::FILE_NAME)]
+    Config(String),
+
+    /// Link checking found broken links. The links themselves are logged as
+    /// they are found.
+    #[error("Found {0} broken link{}", if *.0 != 1 { "s" } else { "" })]
+    BrokenLinks(usize),
+
+    /// Several sources are written to the same output paths, and
+    /// `collisions.precedence` does not settle which one wins. The collisions
+    /// themselves are logged as they are found.
+    #[error("Found {0} output path collision{}", if *.0 != 1 { "s" } else { "" })]
+    Collisions(usize),
+
+    /// A redirect's old path would put its redirect page outside the out
+    /// directory (eg `/../index.html`).
+    #[error("Cannot redirect from `{0}' as it is outside the out directory")]
+    RedirectPath(String),
+
+    /// A path given on the command line is not a source page, static asset,
+    /// or output of the site.
+    #[error("'{}' is not a source page, static asset, or output of this site", .0.display())]
+    UnknownPath(path::PathBuf),
+
     /// Parsing a shell command failed.
     #[error("Cannot parse `{command}': {source}")]
     Shell {
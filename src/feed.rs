@@ -0,0 +1,266 @@
+//! RSS and Atom feed generation.
+
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    config::{self, FeedContent, FeedFormat},
+    html, util, Error, Result,
+};
+
+/// A page to be included in a feed.
+pub(crate) struct Item {
+    /// The title of the page.
+    pub(crate) title: String,
+
+    /// The root-relative url of the page.
+    pub(crate) url: String,
+
+    /// When the page was published.
+    pub(crate) date: DateTime<Utc>,
+
+    /// The html content (or summary) of the page.
+    pub(crate) content: String,
+}
+
+impl Item {
+    /// Creates a feed item for the page generated from `source` at
+    /// `destination`, with the given (transformed) content.
+    ///
+    /// The title is taken from the page's `<title>` or first `<h1>`, and the
+    /// date from a `<meta name="date">` tag, the date prefix of the source file
+    /// name, or the source file's modification time, in that order.
+    pub(crate) fn new(
+        source: &Path,
+        destination: &Path,
+        content: &[u8],
+        out: &Path,
+        mode: FeedContent,
+    ) -> Result<Self> {
+        let content = String::from_utf8_lossy(content);
+        let tokens = html::tokenize(&content);
+
+        let title = html::element_text(&tokens, "title")
+            .filter(|t| !t.is_empty())
+            .or_else(|| html::element_text(&tokens, "h1"))
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| {
+                source
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            });
+
+        let date = util::page_date(source, &tokens)
+            .or_else(|| {
+                fs::metadata(source)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .map(DateTime::from)
+            })
+            .unwrap_or_default();
+
+        let content = match mode {
+            FeedContent::Full => content.into_owned(),
+            FeedContent::Summary => summary(&content).to_owned(),
+        };
+
+        Ok(Self {
+            title,
+            url: util::page_url(destination, out)?,
+            date,
+            content,
+        })
+    }
+}
+
+/// Returns the part of `content` before a `<!-- more -->` comment, or its
+/// first paragraph if there is no such comment.
+fn summary(content: &str) -> &str {
+    if let Some(end) = content.find("<!-- more -->") {
+        return &content[..end];
+    }
+
+    let lower = content.to_ascii_lowercase();
+
+    // `<p` alone would also match tags like `<pre>` and `<picture>`
+    let start = lower.match_indices("<p").map(|(i, _)| i).find(|&i| {
+        lower[i + 2..].starts_with(|c: char| c == '>' || c == '/' || c.is_ascii_whitespace())
+    });
+
+    match start.and_then(|start| Some((start, start + lower[start..].find("</p>")?))) {
+        Some((start, end)) => &content[start..end + 4],
+        None => content,
+    }
+}
+
+/// Writes `feed` with the newest of the given items, returning the path of the
+/// file written.
+pub(crate) fn write(
+    config: &config::Config,
+    feed: &config::Feed,
+    mut items: Vec<Item>,
+    dry_run: bool,
+) -> Result<PathBuf> {
+    let base_url = config
+        .base_url
+        .as_deref()
+        .ok_or_else(|| Error::Config("Feeds require `base_url` to be set".into()))?
+        .trim_end_matches('/');
+
+    items.sort_by_key(|item| std::cmp::Reverse(item.date));
+    items.truncate(feed.items);
+
+    let path = config.dirs.out.join(&feed.path);
+    let feed_url = base_url.to_owned() + &util::page_url(&path, &config.dirs.out)?;
+
+    let xml = match feed.format {
+        FeedFormat::Atom => atom(feed, &items, base_url, &feed_url),
+        FeedFormat::Rss => rss(feed, &items, base_url, &feed_url),
+    };
+
+    log::info!(
+        "{} feed '{}' with {} item{}",
+        util::write_verb(&path, dry_run),
+        path.display(),
+        items.len(),
+        if items.len() != 1 { "s" } else { "" }
+    );
+
+    util::write_file(&path, xml, dry_run)?;
+
+    Ok(path)
+}
+
+/// Returns the contents of an Atom feed.
+fn atom(feed: &config::Feed, items: &[Item], base_url: &str, feed_url: &str) -> String {
+    use util::xml_escape as esc;
+
+    let updated = items.iter().map(|i| i.date).max().unwrap_or_else(Utc::now);
+    let content_tag = match feed.content {
+        FeedContent::Full => "content",
+        FeedContent::Summary => "summary",
+    };
+
+    let mut res = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+    );
+
+    let _ = writeln!(res, "  <title>{}</title>", esc(&feed.title));
+    if !feed.description.is_empty() {
+        let _ = writeln!(res, "  <subtitle>{}</subtitle>", esc(&feed.description));
+    }
+    let _ = writeln!(res, "  <id>{}</id>", esc(feed_url));
+    let _ = writeln!(res, "  <link href=\"{}/\"/>", esc(base_url));
+    let _ = writeln!(res, "  <link href=\"{}\" rel=\"self\"/>", esc(feed_url));
+    let _ = writeln!(res, "  <updated>{}</updated>", updated.to_rfc3339());
+    if let Some(author) = &feed.author {
+        let _ = writeln!(res, "  <author><name>{}</name></author>", esc(author));
+    }
+
+    for item in items {
+        let url = base_url.to_owned() + &item.url;
+
+        res.push_str("  <entry>\n");
+        let _ = writeln!(res, "    <title>{}</title>", esc(&item.title));
+        let _ = writeln!(res, "    <id>{}</id>", esc(&url));
+        let _ = writeln!(res, "    <link href=\"{}\"/>", esc(&url));
+        let _ = writeln!(res, "    <updated>{}</updated>", item.date.to_rfc3339());
+        let _ = writeln!(
+            res,
+            "    <{content_tag} type=\"html\">{}</{content_tag}>",
+            esc(&item.content)
+        );
+        res.push_str("  </entry>\n");
+    }
+
+    res.push_str("</feed>\n");
+    res
+}
+
+/// Returns the contents of an RSS 2.0 feed.
+fn rss(feed: &config::Feed, items: &[Item], base_url: &str, feed_url: &str) -> String {
+    use util::xml_escape as esc;
+
+    let mut res = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n\
+         <channel>\n",
+    );
+
+    let _ = writeln!(res, "  <title>{}</title>", esc(&feed.title));
+    let _ = writeln!(res, "  <link>{}/</link>", esc(base_url));
+    let _ = writeln!(
+        res,
+        "  <description>{}</description>",
+        esc(&feed.description)
+    );
+    let _ = writeln!(
+        res,
+        "  <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>",
+        esc(feed_url)
+    );
+    if let Some(date) = items.iter().map(|i| i.date).max() {
+        let _ = writeln!(
+            res,
+            "  <lastBuildDate>{}</lastBuildDate>",
+            date.to_rfc2822()
+        );
+    }
+
+    for item in items {
+        let url = base_url.to_owned() + &item.url;
+
+        res.push_str("  <item>\n");
+        let _ = writeln!(res, "    <title>{}</title>", esc(&item.title));
+        let _ = writeln!(res, "    <link>{}</link>", esc(&url));
+        let _ = writeln!(res, "    <guid>{}</guid>", esc(&url));
+        let _ = writeln!(res, "    <pubDate>{}</pubDate>", item.date.to_rfc2822());
+        let _ = writeln!(res, "    <description>{}</description>", esc(&item.content));
+        res.push_str("  </item>\n");
+    }
+
+    res.push_str("</channel>\n</rss>\n");
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    //! Tests for picking feed item summaries.
+
+    use super::*;
+
+    /// The summary is the first paragraph, skipping other tags whose names
+    /// start with `p`.
+    #[test]
+    fn first_paragraph() {
+        assert_eq!(
+            summary("<h1>Title</h1><P class=\"lead\">One</P><p>Two</p>"),
+            "<P class=\"lead\">One</P>"
+        );
+        assert_eq!(
+            summary("<pre><code>fn main() {}</code></pre>\n<picture></picture><p>Text</p>"),
+            "<p>Text</p>"
+        );
+        assert_eq!(summary("<p\nid=\"x\">Text</p>"), "<p\nid=\"x\">Text</p>");
+    }
+
+    /// Content up to a `<!-- more -->` comment takes precedence, and content
+    /// without a paragraph is used as it is.
+    #[test]
+    fn fallbacks() {
+        assert_eq!(
+            summary("<p>One</p><p>Two</p><!-- more --><p>Three</p>"),
+            "<p>One</p><p>Two</p>"
+        );
+        assert_eq!(summary("<pre>code</pre>"), "<pre>code</pre>");
+        assert_eq!(summary("</p><pre>code</pre>"), "</p><pre>code</pre>");
+    }
+}
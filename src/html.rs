@@ -0,0 +1,411 @@
+//! A small, forgiving html tokenizer, used to pull titles, text, and links out
+//! of generated pages. It does not build a document tree or validate anything.
+
+/// A piece of an html document.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Token {
+    /// An opening (or self-closing) tag, eg `<a href="/">`. Tag and attribute
+    /// names are lowercased, and attribute values have entities decoded.
+    Start {
+        /// The name of the tag.
+        name: String,
+
+        /// The attributes of the tag, in order, as name-value pairs.
+        attrs: Vec<(String, String)>,
+    },
+
+    /// A closing tag, eg `</a>`. The name is lowercased.
+    End(String),
+
+    /// Text between tags, with entities decoded.
+    Text(String),
+}
+
+impl Token {
+    /// Returns the value of the attribute `name` if this is a start tag that
+    /// has one.
+    pub(crate) fn attr(&self, name: &str) -> Option<&str> {
+        match self {
+            Token::Start { attrs, .. } => attrs
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Returns true if this is a start tag with the given name.
+    pub(crate) fn is_start(&self, tag: &str) -> bool {
+        matches!(self, Token::Start { name, .. } if name == tag)
+    }
+
+    /// Returns true if this is an end tag with the given name.
+    pub(crate) fn is_end(&self, tag: &str) -> bool {
+        matches!(self, Token::End(name) if name == tag)
+    }
+}
+
+/// Splits an html document into tokens. Comments and doctypes are dropped,
+/// and the contents of `<script>` and `<style>` elements are skipped.
+pub(crate) fn tokenize(html: &str) -> Vec<Token> {
+    let mut res = Vec::new();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            res.push(Token::Text(decode_entities(rest)));
+            break;
+        };
+
+        if start > 0 {
+            res.push(Token::Text(decode_entities(&rest[..start])));
+        }
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+        } else if let Some(tag) = rest.strip_prefix("</") {
+            let end = tag.find('>').unwrap_or(tag.len());
+            res.push(Token::End(tag[..end].trim().to_ascii_lowercase()));
+            rest = tag.get(end + 1..).unwrap_or("");
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let (token, len) = start_tag(&rest[1..]);
+            rest = &rest[1 + len..];
+
+            // don't go looking for tags in scripts and stylesheets
+            if let Token::Start { name, .. } = &token {
+                if name == "script" || name == "style" {
+                    let close = format!("</{name}");
+                    let end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+                    rest = &rest[end..];
+                }
+            }
+
+            res.push(token);
+        } else {
+            // a stray `<`, eg in `a < b`
+            res.push(Token::Text("<".into()));
+            rest = &rest[1..];
+        }
+    }
+
+    res
+}
+
+/// Parses a start tag, given the input just after its `<`. Returns the token
+/// and the number of bytes consumed, including the closing `>`.
+fn start_tag(input: &str) -> (Token, usize) {
+    let bytes = input.as_bytes();
+    let mut i = 0;
+
+    let name_end = input
+        .find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+        .unwrap_or(input.len());
+    let name = input[..name_end].to_ascii_lowercase();
+    i += name_end;
+
+    let mut attrs = Vec::new();
+
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+
+        if i >= bytes.len() {
+            break;
+        }
+
+        if bytes[i] == b'>' {
+            i += 1;
+            break;
+        }
+
+        let attr_end = input[i..]
+            .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '>' || c == '/')
+            .map_or(input.len(), |n| i + n);
+        let attr_name = input[i..attr_end].to_ascii_lowercase();
+        i = attr_end;
+
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+
+        let mut value = String::new();
+
+        if i < bytes.len() && bytes[i] == b'=' {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+
+            if i < bytes.len() && (bytes[i] == b'"' || bytes[i] == b'\'') {
+                let quote = bytes[i] as char;
+                let end = input[i + 1..]
+                    .find(quote)
+                    .map_or(input.len(), |n| i + 1 + n);
+                value = decode_entities(&input[i + 1..end]);
+                i = (end + 1).min(input.len());
+            } else {
+                let end = input[i..]
+                    .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                    .map_or(input.len(), |n| i + n);
+                value = decode_entities(&input[i..end]);
+                i = end;
+            }
+        }
+
+        if !attr_name.is_empty() {
+            attrs.push((attr_name, value));
+        }
+    }
+
+    (Token::Start { name, attrs }, i)
+}
+
+/// Decodes the common named entities and all numeric character references in
+/// `text`. Unknown entities are left as-is.
+pub(crate) fn decode_entities(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                res.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                res.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    res.push_str(rest);
+    res
+}
+
+/// Tags whose start and end don't separate words, eg `foo<em>bar</em>`.
+const INLINE_TAGS: &[&str] = &[
+    "a", "abbr", "b", "cite", "code", "em", "i", "kbd", "mark", "q", "s", "samp", "small", "span",
+    "strong", "sub", "sup", "u", "var",
+];
+
+/// Returns the tokens between the start and end tags of the first `tag`
+/// element in `tokens`. Nested elements of the same kind are not handled.
+pub(crate) fn element<'a>(tokens: &'a [Token], tag: &str) -> Option<&'a [Token]> {
+    let start = tokens.iter().position(|t| t.is_start(tag))? + 1;
+    let len = tokens[start..]
+        .iter()
+        .position(|t| t.is_end(tag))
+        .unwrap_or(tokens.len() - start);
+
+    Some(&tokens[start..start + len])
+}
+
+/// Returns the text content of the first `tag` element in `tokens`, with
+/// whitespace collapsed.
+pub(crate) fn element_text(tokens: &[Token], tag: &str) -> Option<String> {
+    element(tokens, tag).map(text)
+}
+
+/// Returns the text content of every heading (`<h1>` through `<h6>`) in
+/// `tokens`, in order.
+pub(crate) fn headings(tokens: &[Token]) -> Vec<String> {
+    let mut res = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        if let Token::Start { name, .. } = token {
+            if matches!(name.as_str(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6") {
+                let heading = element_text(&tokens[i..], name).unwrap_or_default();
+                if !heading.is_empty() {
+                    res.push(heading);
+                }
+            }
+        }
+    }
+
+    res
+}
+
+/// Returns all the text in `tokens`, with whitespace collapsed.
+pub(crate) fn text(tokens: &[Token]) -> String {
+    let mut res = String::new();
+
+    for token in tokens {
+        match token {
+            Token::Text(text) => res.push_str(text),
+            Token::Start { name, .. } | Token::End(name)
+                if INLINE_TAGS.contains(&name.as_str()) => {}
+            // keep the words in adjacent blocks apart
+            _ => res.push(' '),
+        }
+    }
+
+    collapse_whitespace(&res)
+}
+
+/// Returns the tokens holding a page's metadata: the contents of its `<head>`
+/// element if it has one, or else the `<meta>` tags at the very start of the
+/// page, before any other tags or text. This lets a markdown page declare
+/// metadata in its first lines, without examples further down counting.
+pub(crate) fn metadata(tokens: &[Token]) -> &[Token] {
+    if let Some(head) = element(tokens, "head") {
+        return head;
+    }
+
+    let len = tokens
+        .iter()
+        .position(|t| match t {
+            Token::Start { name, .. } => name != "meta",
+            Token::Text(text) => !text.trim().is_empty(),
+            Token::End(_) => true,
+        })
+        .unwrap_or(tokens.len());
+
+    &tokens[..len]
+}
+
+/// Returns the `content` of the first `<meta>` tag with the given `name`.
+pub(crate) fn meta(tokens: &[Token], name: &str) -> Option<String> {
+    tokens
+        .iter()
+        .find(|t| t.is_start("meta") && t.attr("name") == Some(name))
+        .and_then(|t| t.attr("content"))
+        .map(str::to_owned)
+}
+
+/// Replaces every run of whitespace in `text` with a single space, and trims
+/// the ends.
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    //! Tests for tokenizing html.
+
+    use super::*;
+
+    /// Returns a start tag token with the given name and attributes.
+    fn start(name: &str, attrs: &[(&str, &str)]) -> Token {
+        Token::Start {
+            name: name.to_owned(),
+            attrs: attrs
+                .iter()
+                .map(|(n, v)| ((*n).to_owned(), (*v).to_owned()))
+                .collect(),
+        }
+    }
+
+    /// Tags, attributes, and text are split apart, and names are lowercased.
+    #[test]
+    fn tags() {
+        assert_eq!(
+            tokenize(r#"<!DOCTYPE html><A HREF="/x" class=y Hidden>a &amp; b</a><!-- c --><br/>"#),
+            vec![
+                start("a", &[("href", "/x"), ("class", "y"), ("hidden", "")]),
+                Token::Text("a & b".into()),
+                Token::End("a".into()),
+                start("br", &[]),
+            ]
+        );
+    }
+
+    /// Unterminated tags, comments, and attribute values run to the end of the
+    /// input instead of panicking.
+    #[test]
+    fn unterminated() {
+        assert_eq!(
+            tokenize("a <b"),
+            vec![Token::Text("a ".into()), start("b", &[])]
+        );
+        assert_eq!(
+            tokenize(r#"<a href="/x"#),
+            vec![start("a", &[("href", "/x")])]
+        );
+        assert_eq!(tokenize("<a href="), vec![start("a", &[("href", "")])]);
+        assert_eq!(
+            tokenize("x</a"),
+            vec![Token::Text("x".into()), Token::End("a".into())]
+        );
+        assert_eq!(tokenize("x<!-- y"), vec![Token::Text("x".into())]);
+        assert_eq!(
+            tokenize("<script>if (a < b) {}"),
+            vec![start("script", &[])]
+        );
+        assert_eq!(
+            tokenize("1 < 2"),
+            vec![
+                Token::Text("1 ".into()),
+                Token::Text("<".into()),
+                Token::Text(" 2".into()),
+            ]
+        );
+    }
+
+    /// Multibyte text in attributes and text is kept intact, quoted or not.
+    #[test]
+    fn multibyte() {
+        assert_eq!(
+            tokenize(r#"<img alt="日本語 ✓" title=ünïcödé data-x='é'>€"#),
+            vec![
+                start(
+                    "img",
+                    &[("alt", "日本語 ✓"), ("title", "ünïcödé"), ("data-x", "é")]
+                ),
+                Token::Text("€".into()),
+            ]
+        );
+        assert_eq!(
+            tokenize("<p title=\"é"),
+            vec![start("p", &[("title", "é")])]
+        );
+        assert_eq!(
+            tokenize("é<é"),
+            vec![
+                Token::Text("é".into()),
+                Token::Text("<".into()),
+                Token::Text("é".into()),
+            ]
+        );
+    }
+
+    /// Named and numeric entities are decoded, and anything else is left as it
+    /// is.
+    #[test]
+    fn entities() {
+        assert_eq!(
+            decode_entities("&lt;&gt;&quot;&apos;&nbsp;&#233;&#xE9;&#XE9;"),
+            "<>\"'\u{a0}ééé"
+        );
+        assert_eq!(decode_entities("&bogus; & &#xZZ; &"), "&bogus; & &#xZZ; &");
+        assert_eq!(decode_entities("é&amp;é&日本;"), "é&é&日本;");
+        assert_eq!(decode_entities("&#1114112;"), "&#1114112;");
+        assert_eq!(decode_entities("&amp"), "&amp");
+    }
+}
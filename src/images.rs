@@ -0,0 +1,314 @@
+//! Image resizing and conversion, and the template functions that expose it.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageFormat};
+
+use crate::{assets, config, util};
+
+/// Produces resized and converted copies of the images in the static
+/// directory.
+#[derive(Clone, Debug)]
+pub(crate) struct Processor {
+    /// The directory source images are looked up in.
+    static_dir: PathBuf,
+
+    /// The out directory, which processed images are written to.
+    out_dir: PathBuf,
+
+    /// The image settings from the config file.
+    config: config::Images,
+
+    /// Whether to skip writing processed images, only working out their urls.
+    dry_run: bool,
+
+    /// The paths of the processed images used so far, whether they were
+    /// written or already existed. Shared between clones.
+    outputs: Arc<Mutex<BTreeSet<PathBuf>>>,
+
+    /// The paths of the other files the build writes, which processed images
+    /// must not overwrite. Shared between clones.
+    reserved: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl Processor {
+    /// Creates a new image processor from the site config. If `dry_run` is
+    /// set, processed images are not written.
+    pub(crate) fn new(config: &config::Config, dry_run: bool) -> Self {
+        Self {
+            static_dir: config.dirs.r#static.clone(),
+            out_dir: config.dirs.out.clone(),
+            config: config.images.clone(),
+            dry_run,
+            outputs: Arc::default(),
+            reserved: Arc::default(),
+        }
+    }
+
+    /// Sets the paths of the other files the build writes. Processing an image
+    /// into one of them is an error from then on.
+    pub(crate) fn reserve(&self, paths: HashSet<PathBuf>) {
+        *self
+            .reserved
+            .lock()
+            .expect("The reserved paths lock should never be poisoned") = paths;
+    }
+
+    /// Returns the paths of the processed images used so far.
+    pub(crate) fn outputs(&self) -> BTreeSet<PathBuf> {
+        self.outputs
+            .lock()
+            .expect("The image outputs lock should never be poisoned")
+            .clone()
+    }
+
+    /// Resizes the static image at `path` to at most `width` pixels wide and
+    /// encodes it in `format`, returning the url of the result and its actual
+    /// width. Images are never scaled up.
+    ///
+    /// Processed images are named after a hash of the source image, the width,
+    /// the format, and the quality for JPEG images, so unchanged images are not
+    /// decoded or processed again. Paths with `..` components are an error, as
+    /// they could lead outside the static directory.
+    fn process(
+        &self,
+        path: &str,
+        width: Option<u32>,
+        format: Option<&str>,
+    ) -> tera::Result<(String, u32)> {
+        let logical = Path::new(path.trim_start_matches('/'));
+
+        if !logical
+            .components()
+            .all(|part| matches!(part, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(tera::Error::msg(format!(
+                "Image path `{path}' is outside the static directory"
+            )));
+        }
+
+        let source = self.static_dir.join(logical);
+
+        let contents = fs::read(&source).map_err(|e| {
+            tera::Error::chain(format!("Cannot read image '{}'", source.display()), e)
+        })?;
+
+        let format = match format {
+            Some(format) => ImageFormat::from_extension(format)
+                .ok_or_else(|| tera::Error::msg(format!("Unknown image format '{format}'")))?,
+            None => image::guess_format(&contents).map_err(|e| {
+                tera::Error::chain(format!("Cannot guess format of '{}'", source.display()), e)
+            })?,
+        };
+        let ext = format.extensions_str()[0];
+
+        // only the header is read here, so cached images are never decoded
+        let (full_width, _) = image::ImageReader::new(io::Cursor::new(&contents))
+            .with_guessed_format()
+            .map_err(|e| {
+                tera::Error::chain(format!("Cannot read image '{}'", source.display()), e)
+            })?
+            .into_dimensions()
+            .map_err(|e| {
+                tera::Error::chain(format!("Cannot decode image '{}'", source.display()), e)
+            })?;
+        let width = width.map_or(full_width, |w| w.min(full_width));
+
+        // webp images are always encoded losslessly, so only jpeg images are
+        // named after the quality
+        let quality = match format {
+            ImageFormat::Jpeg => format!(".q{}", self.config.quality),
+            _ => String::new(),
+        };
+
+        let mut name = logical.file_stem().unwrap_or_default().to_owned();
+        name.push(format!(
+            ".{}.{width}w{quality}.{ext}",
+            &util::hex_digest(&contents)[..assets::HASH_LEN]
+        ));
+
+        let relative = self.config.dir.join(logical.with_file_name(name));
+        let destination = self.out_dir.join(&relative);
+
+        if self
+            .reserved
+            .lock()
+            .expect("The reserved paths lock should never be poisoned")
+            .contains(&destination)
+        {
+            return Err(tera::Error::msg(format!(
+                "Processing '{}' would overwrite '{}', which is written by another source",
+                source.display(),
+                destination.display()
+            )));
+        }
+
+        if destination.exists() {
+            log::debug!(
+                "Using cached image '{}' for '{}'",
+                destination.display(),
+                source.display()
+            );
+        } else if self.dry_run {
+            log::info!(
+                "Would process '{}' into '{}'",
+                source.display(),
+                destination.display()
+            );
+        } else {
+            log::info!(
+                "Processing '{}' into '{}'",
+                source.display(),
+                destination.display()
+            );
+
+            let img = image::load_from_memory(&contents).map_err(|e| {
+                tera::Error::chain(format!("Cannot decode image '{}'", source.display()), e)
+            })?;
+
+            let img = if width < full_width {
+                img.resize(width, u32::MAX, FilterType::Lanczos3)
+            } else {
+                img
+            };
+
+            self.write(&img, format, &destination)?;
+        }
+
+        self.outputs
+            .lock()
+            .expect("The image outputs lock should never be poisoned")
+            .insert(destination);
+
+        let url = util::to_url_path(&relative).map_err(|e| tera::Error::msg(e.to_string()))?;
+
+        Ok((self.config.prefix.clone() + &url, width))
+    }
+
+    /// Encodes `img` in the given format and writes it to `destination`.
+    fn write(
+        &self,
+        img: &DynamicImage,
+        format: ImageFormat,
+        destination: &Path,
+    ) -> tera::Result<()> {
+        if let Some(p) = destination.parent() {
+            fs::create_dir_all(p)
+                .map_err(|e| tera::Error::chain(format!("Cannot create '{}'", p.display()), e))?;
+        }
+
+        let file = fs::File::create(destination).map_err(|e| {
+            tera::Error::chain(format!("Cannot create '{}'", destination.display()), e)
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        // jpeg has no alpha channel and the webp encoder only supports 8-bit
+        // color, so convert to something each encoder can handle
+        let res = match format {
+            ImageFormat::Jpeg => DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(
+                JpegEncoder::new_with_quality(&mut writer, self.config.quality),
+            ),
+            ImageFormat::WebP => {
+                DynamicImage::ImageRgba8(img.to_rgba8()).write_to(&mut writer, ImageFormat::WebP)
+            }
+            format => img.write_to(&mut writer, format),
+        };
+
+        res.map_err(|e| {
+            tera::Error::chain(format!("Cannot encode '{}'", destination.display()), e)
+        })?;
+
+        writer
+            .flush()
+            .map_err(|e| tera::Error::chain(format!("Cannot write '{}'", destination.display()), e))
+    }
+}
+
+/// Returns the string argument `name` from a template function's arguments, if
+/// present.
+fn str_arg<'a>(args: &'a HashMap<String, tera::Value>, name: &str) -> Option<&'a str> {
+    args.get(name).and_then(tera::Value::as_str)
+}
+
+/// Returns the `resize_image` template function, which resizes and converts an
+/// image from the static directory and returns the url of the result. Both
+/// `width` and `format` are optional.
+///
+/// ## Example
+/// ```html
+/// <img src="{{ resize_image(path="photos/cat.jpg", width=800, format="webp") }}" />
+/// ```
+pub(crate) fn resize_image(processor: Processor) -> impl tera::Function {
+    move |args: &HashMap<String, tera::Value>| {
+        let path = str_arg(args, "path")
+            .ok_or_else(|| tera::Error::msg("`resize_image` requires a string `path` argument"))?;
+
+        let width = match args.get("width") {
+            None => None,
+            Some(width) => Some(
+                width
+                    .as_u64()
+                    .and_then(|w| u32::try_from(w).ok())
+                    .filter(|&w| w > 0)
+                    .ok_or_else(|| {
+                        tera::Error::msg("`resize_image`: `width` must be a positive integer")
+                    })?,
+            ),
+        };
+
+        let (url, _) = processor.process(path, width, str_arg(args, "format"))?;
+
+        Ok(tera::Value::String(url))
+    }
+}
+
+/// Returns the `srcset` template function, which resizes an image to each of
+/// the given widths and returns a value for an `<img>` tag's `srcset`
+/// attribute.
+///
+/// ## Example
+/// ```html
+/// <img srcset="{{ srcset(path="photos/cat.jpg", widths=[480, 960, 1920]) }}" />
+/// ```
+pub(crate) fn srcset(processor: Processor) -> impl tera::Function {
+    move |args: &HashMap<String, tera::Value>| {
+        let path = str_arg(args, "path")
+            .ok_or_else(|| tera::Error::msg("`srcset` requires a string `path` argument"))?;
+
+        let widths = args
+            .get("widths")
+            .and_then(tera::Value::as_array)
+            .ok_or_else(|| tera::Error::msg("`srcset` requires an array `widths` argument"))?;
+
+        let mut candidates: Vec<(String, u32)> = Vec::new();
+
+        for width in widths {
+            let width = width
+                .as_u64()
+                .and_then(|w| u32::try_from(w).ok())
+                .filter(|&w| w > 0)
+                .ok_or_else(|| tera::Error::msg("`srcset`: `widths` must be positive integers"))?;
+
+            let candidate = processor.process(path, Some(width), str_arg(args, "format"))?;
+
+            // widths larger than the image all collapse to the original size
+            if !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+
+        Ok(tera::Value::String(
+            candidates
+                .iter()
+                .map(|(url, width)| format!("{url} {width}w"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ))
+    }
+}
@@ -0,0 +1,435 @@
+//! Checking the links in generated html pages.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{config, html, util, Error, Result};
+
+mod external;
+
+/// Attributes that hold a single link, by the tags they appear on.
+const LINK_ATTRS: &[(&str, &str)] = &[
+    ("a", "href"),
+    ("area", "href"),
+    ("link", "href"),
+    ("audio", "src"),
+    ("embed", "src"),
+    ("iframe", "src"),
+    ("img", "src"),
+    ("script", "src"),
+    ("source", "src"),
+    ("track", "src"),
+    ("video", "src"),
+    ("video", "poster"),
+];
+
+/// A link found in a generated page.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Link {
+    /// The page the link appears in (eg `out/index.html`).
+    pub(crate) page: PathBuf,
+
+    /// The link exactly as it appears in the page, after entity decoding.
+    pub(crate) href: String,
+}
+
+/// Returns every link in the html page at `page`.
+pub(crate) fn links(page: &Path) -> Result<Vec<Link>> {
+    let tokens = read_tokens(page)?;
+    let mut res = Vec::new();
+
+    for token in &tokens {
+        let html::Token::Start { name, .. } = token else {
+            continue;
+        };
+
+        for (tag, attr) in LINK_ATTRS {
+            if name == tag {
+                if let Some(href) = token.attr(attr) {
+                    res.push(href.trim().to_owned());
+                }
+            }
+        }
+
+        // srcsets are comma-separated lists of `url width` pairs
+        if let Some(srcset) = token.attr("srcset") {
+            for candidate in srcset.split(',') {
+                if let Some(href) = candidate.split_whitespace().next() {
+                    res.push(href.to_owned());
+                }
+            }
+        }
+    }
+
+    Ok(res
+        .into_iter()
+        .filter(|href| !href.is_empty())
+        .map(|href| Link {
+            page: page.to_owned(),
+            href,
+        })
+        .collect())
+}
+
+/// Returns true if `href` points outside the site, ie it has a scheme (like
+/// `https:` or `mailto:`) or is protocol-relative.
+pub(crate) fn is_external(href: &str) -> bool {
+    if href.starts_with("//") {
+        return true;
+    }
+
+    match href.find(':') {
+        Some(colon) => href[..colon]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')),
+        None => false,
+    }
+}
+
+/// Checks internal links in generated pages, remembering the anchors of every
+/// page it has read.
+pub(crate) struct Checker {
+    /// The out directory, which root-relative links are resolved against.
+    out: PathBuf,
+
+    /// The path the site is served under, taken from `base_url` (eg
+    /// `/mksite` for `https://alterae.github.io/mksite`). Stripped from
+    /// root-relative links before resolving them.
+    base_path: String,
+
+    /// The `id`s (and `<a name>`s) of every page read so far.
+    anchors: HashMap<PathBuf, HashSet<String>>,
+}
+
+impl Checker {
+    /// Creates a checker for the site in `out`, served at `base_url`.
+    pub(crate) fn new(out: &Path, base_url: Option<&str>) -> Self {
+        let base_path = base_url
+            .and_then(|url| url.split_once("://"))
+            .and_then(|(_, rest)| rest.find('/').map(|slash| &rest[slash..]))
+            .unwrap_or("")
+            .trim_end_matches('/')
+            .to_owned();
+
+        Self {
+            out: out.to_owned(),
+            base_path,
+            anchors: HashMap::new(),
+        }
+    }
+
+    /// Checks an internal link, returning a description of the problem if it
+    /// is broken. External links are always considered fine.
+    pub(crate) fn check(&mut self, link: &Link) -> Result<Option<String>> {
+        if is_external(&link.href) {
+            return Ok(None);
+        }
+
+        let (path, fragment) = match link.href.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (link.href.as_str(), None),
+        };
+        let path = path.split('?').next().unwrap_or_default();
+
+        let target = if path.is_empty() {
+            link.page.clone()
+        } else {
+            match self.resolve(&link.page, path) {
+                Some(target) => target,
+                None => return Ok(Some("points outside the out directory".into())),
+            }
+        };
+
+        if !target.is_file() {
+            return Ok(Some(format!("'{}' does not exist", target.display())));
+        }
+
+        let Some(fragment) = fragment.filter(|f| !f.is_empty()) else {
+            return Ok(None);
+        };
+
+        // only html pages have anchors to check
+        if !matches!(
+            target.extension().and_then(OsStr::to_str),
+            Some("html" | "htm")
+        ) {
+            return Ok(None);
+        }
+
+        if !self.anchors.contains_key(&target) {
+            let anchors = anchors(&read_tokens(&target)?);
+            self.anchors.insert(target.clone(), anchors);
+        }
+
+        let fragment = percent_decode(fragment);
+
+        if fragment == "top" || self.anchors[&target].contains(&fragment) {
+            Ok(None)
+        } else {
+            Ok(Some(format!(
+                "'{}' has no anchor '#{fragment}'",
+                target.display()
+            )))
+        }
+    }
+
+    /// Resolves a link path relative to `page` into a path in the out
+    /// directory. Links to directories resolve to their `index.html`. Returns
+    /// `None` if the link leads outside the out directory.
+    fn resolve(&self, page: &Path, path: &str) -> Option<PathBuf> {
+        let path = percent_decode(path);
+
+        let (base, relative) = match path.strip_prefix('/') {
+            Some(rooted) => {
+                let rooted = match rooted.strip_prefix(self.base_path.trim_start_matches('/')) {
+                    Some(rest)
+                        if !self.base_path.is_empty()
+                            && (rest.is_empty() || rest.starts_with('/')) =>
+                    {
+                        rest.trim_start_matches('/')
+                    }
+                    _ => rooted,
+                };
+                (self.out.clone(), rooted.to_owned())
+            }
+            None => (page.parent()?.to_owned(), path.clone()),
+        };
+
+        // resolve `.` and `..` without touching the file system, and without
+        // escaping the out directory
+        let mut target = base;
+        for component in Path::new(&relative).components() {
+            match component {
+                Component::ParentDir if target == self.out || !target.pop() => return None,
+                Component::Normal(part) => target.push(part),
+                _ => {}
+            }
+        }
+
+        if path.ends_with('/') || target.is_dir() {
+            target.push("index.html");
+        }
+
+        Some(target)
+    }
+}
+
+/// Returns every `id` and `<a name>` in a page.
+fn anchors(tokens: &[html::Token]) -> HashSet<String> {
+    let mut res = HashSet::new();
+
+    for token in tokens {
+        if let Some(id) = token.attr("id") {
+            res.insert(id.to_owned());
+        }
+
+        if token.is_start("a") {
+            if let Some(name) = token.attr("name") {
+                res.insert(name.to_owned());
+            }
+        }
+    }
+
+    res
+}
+
+/// Reads and tokenizes the html page at `page`.
+fn read_tokens(page: &Path) -> Result<Vec<html::Token>> {
+    let content = fs::read(page).map_err(|source| Error::Io {
+        msg: format!("Cannot read '{}'", page.display()),
+        source,
+    })?;
+
+    Ok(html::tokenize(&String::from_utf8_lossy(&content)))
+}
+
+/// Decodes `%XX` escapes in a url path. Invalid escapes are left as-is.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(b) => {
+                res.push(b);
+                i += 3;
+            }
+            None => {
+                res.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+/// Checks the links in every html page in the out directory, and logs an error
+/// for each broken one. Links to other sites are only checked if `external`
+/// is true. `origins` maps generated files to the source files they came from,
+/// for more useful error messages. Returns the number of broken links.
+pub(crate) fn check(
+    config: &config::Config,
+    origins: &HashMap<PathBuf, PathBuf>,
+    external: bool,
+) -> Result<usize> {
+    let mut checker = Checker::new(&config.dirs.out, config.base_url.as_deref());
+    let mut broken = 0;
+    let mut external_links = Vec::new();
+    let mut pages = util::walk_dir(&config.dirs.out)?
+        .into_iter()
+        .filter(|p| matches!(p.extension().and_then(OsStr::to_str), Some("html" | "htm")))
+        .collect::<Vec<_>>();
+    pages.sort();
+
+    let report = |link: &Link, problem: &str| {
+        log::error!(
+            "Broken link `{}' in '{}'{}: {problem}",
+            link.href,
+            link.page.display(),
+            origins
+                .get(&link.page)
+                .map(|source| format!(" (from '{}')", source.display()))
+                .unwrap_or_default()
+        );
+    };
+
+    for page in pages {
+        log::debug!("Checking links in '{}'", page.display());
+
+        for link in links(&page)? {
+            if is_external(&link.href) {
+                if link.href.starts_with("http://") || link.href.starts_with("https://") {
+                    external_links.push(link);
+                }
+            } else if let Some(problem) = checker.check(&link)? {
+                broken += 1;
+                report(&link, &problem);
+            }
+        }
+    }
+
+    if external && !external_links.is_empty() {
+        let url = |link: &Link| link.href.split('#').next().unwrap_or_default().to_owned();
+
+        let mut urls = external_links.iter().map(url).collect::<Vec<_>>();
+        urls.sort();
+        urls.dedup();
+
+        let results = external::check(&config.links, &urls)?;
+
+        for link in &external_links {
+            if let Some(Some(problem)) = results.get(&url(link)) {
+                broken += 1;
+                report(link, problem);
+            }
+        }
+    }
+
+    Ok(broken)
+}
+
+#[cfg(test)]
+mod tests {
+    //! Tests for resolving internal links.
+
+    use super::*;
+
+    /// Resolves `path` as linked from `page` on a site in the nonexistent
+    /// directory `out`, served at `base_url`.
+    fn resolve(base_url: Option<&str>, page: &str, path: &str) -> Option<PathBuf> {
+        Checker::new(Path::new("out"), base_url).resolve(Path::new(page), path)
+    }
+
+    /// Relative links resolve against the linking page's directory, and
+    /// root-relative ones against the out directory.
+    #[test]
+    fn relative_and_rooted() {
+        assert_eq!(
+            resolve(None, "out/blog/post.html", "other.html"),
+            Some("out/blog/other.html".into())
+        );
+        assert_eq!(
+            resolve(None, "out/blog/post.html", "./a/../other.html"),
+            Some("out/blog/other.html".into())
+        );
+        assert_eq!(
+            resolve(None, "out/blog/post.html", "../about.html"),
+            Some("out/about.html".into())
+        );
+        assert_eq!(
+            resolve(None, "out/blog/post.html", "/about.html"),
+            Some("out/about.html".into())
+        );
+        assert_eq!(
+            resolve(None, "out/index.html", "/a%20b.html"),
+            Some("out/a b.html".into())
+        );
+    }
+
+    /// Links ending in `/` resolve to the directory's `index.html`.
+    #[test]
+    fn directories() {
+        assert_eq!(
+            resolve(None, "out/index.html", "/blog/"),
+            Some("out/blog/index.html".into())
+        );
+        assert_eq!(
+            resolve(None, "out/blog/post.html", "./"),
+            Some("out/blog/index.html".into())
+        );
+    }
+
+    /// The path of `base_url` is stripped from root-relative links, but only
+    /// as a whole segment.
+    #[test]
+    fn base_path() {
+        let base_url = Some("https://example.com/docs/");
+
+        assert_eq!(
+            resolve(base_url, "out/index.html", "/docs/about.html"),
+            Some("out/about.html".into())
+        );
+        assert_eq!(
+            resolve(base_url, "out/index.html", "/docs/"),
+            Some("out/index.html".into())
+        );
+        assert_eq!(
+            resolve(base_url, "out/index.html", "/docsearch.html"),
+            Some("out/docsearch.html".into())
+        );
+        assert_eq!(
+            resolve(
+                Some("https://example.com"),
+                "out/index.html",
+                "/docs/about.html"
+            ),
+            Some("out/docs/about.html".into())
+        );
+    }
+
+    /// Links leading outside the out directory don't resolve.
+    #[test]
+    fn outside() {
+        assert_eq!(resolve(None, "out/index.html", "../secret.html"), None);
+        assert_eq!(resolve(None, "out/index.html", "/../secret.html"), None);
+        assert_eq!(
+            resolve(None, "out/blog/post.html", "../../secret.html"),
+            None
+        );
+        assert_eq!(
+            resolve(None, "out/blog/post.html", "/blog/../../secret.html"),
+            None
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Checking links to other sites.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{config, Error, Result};
+
+/// The result of checking an external url, as remembered in the cache.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+struct Record {
+    /// When the url was checked, in seconds since the unix epoch.
+    checked: u64,
+
+    /// A description of what is wrong with the url, if it is broken.
+    problem: Option<String>,
+}
+
+/// Checks each of `urls`, returning a description of the problem with each
+/// broken one. Urls that are not allowed by the config are skipped and left out
+/// of the result, and urls checked recently enough are not checked again.
+pub(crate) fn check(
+    config: &config::Links,
+    urls: &[String],
+) -> Result<HashMap<String, Option<String>>> {
+    let allow = glob_set(&config.allow)?;
+    let deny = glob_set(&config.deny)?;
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut cache = load_cache(config);
+    let max_age = config.max_age.saturating_mul(60 * 60);
+
+    let mut res = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    for url in urls {
+        if (!config.allow.is_empty() && !allow.is_match(url)) || deny.is_match(url) {
+            log::debug!("Skipping external link `{url}'");
+            continue;
+        }
+
+        match cache.get(url) {
+            Some(record) if now.saturating_sub(record.checked) < max_age => {
+                log::debug!("Using cached result for `{url}'");
+                res.insert(url.clone(), record.problem.clone());
+            }
+            _ => queue.push_back(url.clone()),
+        }
+    }
+
+    log::info!(
+        "Checking {} external link{} ({} cached)",
+        queue.len(),
+        if queue.len() != 1 { "s" } else { "" },
+        res.len()
+    );
+
+    let mut builder = ureq::AgentBuilder::new().timeout(Duration::from_secs(config.timeout));
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(
+            ureq::Proxy::new(proxy)
+                .map_err(|e| Error::Config(format!("Invalid proxy `{proxy}': {e}")))?,
+        );
+    }
+    let agent = builder.build();
+
+    let workers = config.concurrency.clamp(1, queue.len().max(1));
+    let queue = Mutex::new(queue);
+    let results = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while let Some(url) = queue.lock().expect("poisoned queue").pop_front() {
+                    let (problem, cacheable) = check_url(&agent, config, &url);
+                    results
+                        .lock()
+                        .expect("poisoned results")
+                        .push((url, problem, cacheable));
+                }
+            });
+        }
+    });
+
+    for (url, problem, cacheable) in results.into_inner().expect("poisoned results") {
+        if cacheable {
+            cache.insert(
+                url.clone(),
+                Record {
+                    checked: now,
+                    problem: problem.clone(),
+                },
+            );
+        }
+        res.insert(url, problem);
+    }
+
+    save_cache(config, &cache)?;
+
+    Ok(res)
+}
+
+/// Checks a single url, retrying on network errors, server errors, and rate
+/// limiting. Returns a description of the problem if the url is broken, and
+/// whether the result should be cached. Network errors, server errors, and
+/// rate limiting are not cached, so a flaky connection or a busy server doesn't
+/// mark links as broken for a whole day.
+fn check_url(agent: &ureq::Agent, config: &config::Links, url: &str) -> (Option<String>, bool) {
+    // the longest matching prefix wins, so the result doesn't depend on the
+    // order of the map
+    let target = config
+        .rewrite
+        .iter()
+        .filter_map(|(from, to)| {
+            url.strip_prefix(from.as_str())
+                .map(|rest| (from.len(), to.clone() + rest))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map_or_else(|| url.to_owned(), |(_, target)| target);
+
+    let mut attempt = 0;
+
+    loop {
+        log::debug!("Requesting `{target}'");
+
+        // some servers don't support HEAD requests, so fall back to GET
+        let res = match agent.head(&target).call() {
+            Err(ureq::Error::Status(403 | 405 | 501, _)) => agent.get(&target).call(),
+            res => res,
+        };
+
+        let (problem, cacheable) = match res {
+            Ok(_) => return (None, true),
+            Err(ureq::Error::Status(code, _)) if code == 429 || code >= 500 => {
+                (format!("HTTP status {code}"), false)
+            }
+            Err(ureq::Error::Status(code, _)) => {
+                return (Some(format!("HTTP status {code}")), true)
+            }
+            Err(ureq::Error::Transport(e)) => (e.to_string(), false),
+        };
+
+        if attempt >= config.retries {
+            return (Some(problem), cacheable);
+        }
+
+        attempt += 1;
+        log::debug!("Retrying `{target}' after error: {problem}");
+        // back off exponentially, waiting at most 32 seconds between attempts
+        thread::sleep(Duration::from_millis(500 << attempt.min(6)));
+    }
+}
+
+/// Compiles a list of url globs, in which `*` matches any characters
+/// (including `/`).
+fn glob_set(patterns: &[String]) -> Result<globset::GlobSet> {
+    let mut builder = globset::GlobSetBuilder::new();
+
+    for pattern in patterns {
+        builder.add(
+            globset::Glob::new(pattern)
+                .map_err(|e| Error::Config(format!("Invalid glob `{pattern}': {e}")))?,
+        );
+    }
+
+    builder
+        .build()
+        .map_err(|e| Error::Config(format!("Invalid globs: {e}")))
+}
+
+/// Loads the link cache. A missing or unreadable cache is treated as empty.
+fn load_cache(config: &config::Links) -> HashMap<String, Record> {
+    let Ok(contents) = fs::read_to_string(&config.cache) else {
+        return HashMap::new();
+    };
+
+    serde_json::from_str(&contents).unwrap_or_else(|e| {
+        log::warn!(
+            "Ignoring invalid link cache '{}': {e}",
+            config.cache.display()
+        );
+        HashMap::new()
+    })
+}
+
+/// Saves the link cache.
+fn save_cache(config: &config::Links, cache: &HashMap<String, Record>) -> Result<()> {
+    if let Some(p) = config.cache.parent() {
+        fs::create_dir_all(p).map_err(|source| Error::Io {
+            msg: format!("Cannot create '{}'", p.display()),
+            source,
+        })?;
+    }
+
+    fs::write(
+        &config.cache,
+        serde_json::to_string_pretty(cache)
+            .expect("Serializing the link cache should be infallible"),
+    )
+    .map_err(|source| Error::Io {
+        msg: format!("Cannot write '{}'", config.cache.display()),
+        source,
+    })
+}
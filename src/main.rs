@@ -5,10 +5,21 @@ use std::process::exit;
 
 use clap::Parser;
 
+mod assets;
 mod cli;
 mod config;
+mod env;
 mod error;
+mod feed;
+mod html;
+mod images;
+mod links;
+mod permalink;
+mod prune;
+mod redirect;
+mod search;
 mod site;
+mod sitemap;
 mod transform;
 mod util;
 
@@ -19,7 +30,7 @@ fn main() {
 
     setup_logger(args.log_level, args.quiet).unwrap_or_else(|e| log::error!("{e}"));
 
-    args.command.run().unwrap_or_else(|e| {
+    args.run().unwrap_or_else(|e| {
         log::error!("{e}");
         exit(1)
     });
@@ -0,0 +1,157 @@
+//! Expanding permalink patterns into output paths.
+
+use std::path::{Path, PathBuf};
+
+use chrono::Datelike;
+
+use crate::{util, Error, Result};
+
+/// Expands a permalink pattern (eg `/blog/{year}/{month}/{slug}/`) into the
+/// path a page is written to. `source` is the page's source file (eg
+/// `src/blog/2024-01-31-hello.md`), and `mirrored` is the path that mirrors it
+/// in the out directory, which provides the output extension.
+///
+/// Supported placeholders are `{year}`, `{month}`, and `{day}` from a date
+/// prefix on the file name, `{slug}` for the file name without that prefix or
+/// extension, `{stem}` for the whole file name without extension, `{ext}` for
+/// the output extension, and `{path}` for the directory of the mirrored path
+/// relative to the out directory (usually the source file's directory relative
+/// to the src directory).
+///
+/// Patterns ending in `/` produce an `index` file in that directory, and
+/// patterns whose last segment has no extension get the output extension.
+/// Patterns that expand to a path with `..` segments are an error, as they
+/// could lead outside the out directory.
+pub(crate) fn expand(pattern: &str, source: &Path, mirrored: &Path, out: &Path) -> Result<PathBuf> {
+    let stem = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| Error::PathConversion(source.to_owned()))?;
+    let ext = mirrored
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let date = util::date_prefix(source);
+
+    let slug = match date {
+        Some(_) => stem[10..].trim_start_matches(['-', '_']),
+        None => stem,
+    };
+
+    let dir = util::swap_prefix(mirrored, out, "")?;
+    let dir = util::to_url_path(dir.parent().unwrap_or(Path::new("")))?;
+
+    let mut res = pattern.to_owned();
+
+    for (placeholder, value) in [
+        ("{slug}", Some(slug.to_owned())),
+        ("{stem}", Some(stem.to_owned())),
+        ("{ext}", Some(ext.to_owned())),
+        ("{path}", Some(dir)),
+        ("{year}", date.map(|d| format!("{:04}", d.year()))),
+        ("{month}", date.map(|d| format!("{:02}", d.month()))),
+        ("{day}", date.map(|d| format!("{:02}", d.day()))),
+    ] {
+        if !res.contains(placeholder) {
+            continue;
+        }
+
+        let value = value.ok_or_else(|| {
+            Error::Config(format!(
+                "Permalink `{pattern}' uses {placeholder}, but '{}' has no date prefix",
+                source.display()
+            ))
+        })?;
+
+        res = res.replace(placeholder, &value);
+    }
+
+    if res.ends_with('/') {
+        res.push_str("index");
+    }
+
+    let last = res.rsplit('/').next().unwrap_or_default();
+    if !last.contains('.') && !ext.is_empty() {
+        res.push('.');
+        res.push_str(ext);
+    }
+
+    if res.split('/').any(|part| part == "..") {
+        return Err(Error::Config(format!(
+            "Permalink `{pattern}' leads outside the out directory for '{}'",
+            source.display()
+        )));
+    }
+
+    // `{path}` may be empty, which would leave a double slash behind
+    let res = res
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect::<PathBuf>();
+
+    Ok(out.join(res))
+}
+
+#[cfg(test)]
+mod tests {
+    //! Tests for expanding permalink patterns.
+
+    use super::*;
+
+    /// Expands `pattern` for `source`, which is mirrored to `mirrored`, in the
+    /// `out` directory.
+    fn expand_in_out(pattern: &str, source: &str, mirrored: &str) -> Result<PathBuf> {
+        expand(
+            pattern,
+            Path::new(source),
+            Path::new(mirrored),
+            Path::new("out"),
+        )
+    }
+
+    /// Date placeholders come from the date prefix, which is left out of the
+    /// slug.
+    #[test]
+    fn date_prefixed() {
+        assert_eq!(
+            expand_in_out(
+                "/blog/{year}/{month}/{day}/{slug}/",
+                "src/posts/2024-01-31-hello.md",
+                "out/posts/2024-01-31-hello.html"
+            )
+            .unwrap(),
+            Path::new("out/blog/2024/01/31/hello/index.html")
+        );
+        assert_eq!(
+            expand_in_out(
+                "/{path}/{stem}",
+                "src/posts/2024-01-31_hello.md",
+                "out/posts/2024-01-31_hello.html"
+            )
+            .unwrap(),
+            Path::new("out/posts/2024-01-31_hello.html")
+        );
+    }
+
+    /// Stems without a date prefix are used as the slug as they are, and can't
+    /// be used with date placeholders.
+    #[test]
+    fn bare() {
+        assert_eq!(
+            expand_in_out("/{slug}.{ext}", "src/about.md", "out/about.html").unwrap(),
+            Path::new("out/about.html")
+        );
+        assert_eq!(
+            expand_in_out("/{path}/{slug}/", "src/about.md", "out/about.html").unwrap(),
+            Path::new("out/about/index.html")
+        );
+        assert!(expand_in_out("/{year}/{slug}/", "src/about.md", "out/about.html").is_err());
+    }
+
+    /// Patterns can't lead outside the out directory.
+    #[test]
+    fn parent_dir() {
+        assert!(expand_in_out("/../{slug}", "src/about.md", "out/about.html").is_err());
+        assert!(expand_in_out("/{path}/../../x/", "src/a/about.md", "out/a/about.html").is_err());
+    }
+}
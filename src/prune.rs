@@ -0,0 +1,187 @@
+//! Deleting stale files from the out directory.
+
+use std::{collections::HashSet, fs, path::Path, path::PathBuf};
+
+use crate::{util, Error, Result};
+
+/// Deletes every file in `out` that is not in `written` or matched by one of
+/// the `keep` globs, along with any directories left empty. Symlinks are
+/// never followed: a stale symlink is deleted itself, leaving what it points
+/// to alone, and nothing outside `out` is ever deleted. In a dry run, the
+/// files that would be deleted are only logged. Returns the number of stale
+/// files found.
+pub(crate) fn prune<S: AsRef<str>>(
+    out: &Path,
+    written: &HashSet<PathBuf>,
+    keep: &[S],
+    dry_run: bool,
+) -> Result<usize> {
+    if !out.exists() {
+        return Ok(0);
+    }
+
+    let keep = util::glob_set(keep)?;
+    let canonical_out = out.canonicalize().map_err(|source| Error::Io {
+        msg: format!("Cannot resolve '{}'", out.display()),
+        source,
+    })?;
+    let mut pruned = 0;
+
+    for file in util::walk_dir_no_follow(out)? {
+        if written.contains(&file) {
+            continue;
+        }
+
+        // only the directory is resolved, so symlinks themselves are checked
+        // (and deleted) rather than their targets
+        if !file
+            .parent()
+            .and_then(|dir| dir.canonicalize().ok())
+            .is_some_and(|dir| dir.starts_with(&canonical_out))
+        {
+            log::warn!(
+                "Not deleting '{}' as it does not resolve to a path in '{}'",
+                file.display(),
+                out.display()
+            );
+            continue;
+        }
+
+        if keep.is_match(util::swap_prefix(&file, out, "")?) {
+            log::debug!(
+                "Keeping '{}' as it is matched by `prune.keep'",
+                file.display()
+            );
+            continue;
+        }
+
+        pruned += 1;
+
+        if dry_run {
+            log::info!("Would delete stale file '{}'", file.display());
+            continue;
+        }
+
+        log::info!("Deleting stale file '{}'", file.display());
+
+        fs::remove_file(&file).map_err(|source| Error::Io {
+            msg: format!("Cannot delete '{}'", file.display()),
+            source,
+        })?;
+    }
+
+    if !dry_run {
+        util::remove_empty_dirs(out)?;
+    }
+
+    log::info!(
+        "{} {pruned} stale file{}",
+        if dry_run { "Would prune" } else { "Pruned" },
+        if pruned != 1 { "s" } else { "" }
+    );
+
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    //! Tests for pruning stale files.
+
+    use super::*;
+
+    /// Creates an empty scratch directory for the test `name`, replacing any
+    /// left over from an earlier run.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mksite-prune-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Creates the file `path`, along with its parent directories.
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    /// Files that were written or are matched by `keep` survive, and the rest
+    /// are deleted along with the directories left empty.
+    #[test]
+    fn keep_and_empty_dirs() {
+        let dir = scratch("keep");
+        let out = dir.join("out");
+
+        for path in [
+            "index.html",
+            "stale.html",
+            "old/deep/page.html",
+            "CNAME",
+            ".git/HEAD",
+            "assets/kept.css",
+            "assets/stale.css",
+        ] {
+            touch(&out.join(path));
+        }
+        fs::create_dir_all(out.join("empty")).unwrap();
+
+        let written = HashSet::from([out.join("index.html"), out.join("assets/kept.css")]);
+        let pruned = prune(&out, &written, &["CNAME", ".git"], false).unwrap();
+
+        assert_eq!(pruned, 3);
+        for path in ["index.html", "CNAME", ".git/HEAD", "assets/kept.css"] {
+            assert!(out.join(path).exists(), "{path} should be kept");
+        }
+        for path in ["stale.html", "old", "assets/stale.css", "empty"] {
+            assert!(!out.join(path).exists(), "{path} should be deleted");
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// A dry run deletes nothing.
+    #[test]
+    fn dry_run() {
+        let dir = scratch("dry-run");
+        let out = dir.join("out");
+        touch(&out.join("stale/page.html"));
+
+        assert_eq!(
+            prune(&out, &HashSet::new(), &[] as &[&str], true).unwrap(),
+            1
+        );
+        assert!(out.join("stale/page.html").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Stale symlinks are deleted themselves, without following them, and
+    /// symlinked directories are never descended into.
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        let dir = scratch("symlinks");
+        let out = dir.join("out");
+        let outside = dir.join("outside");
+        touch(&outside.join("precious.txt"));
+        fs::create_dir_all(&out).unwrap();
+
+        std::os::unix::fs::symlink(&outside, out.join("dir-link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("precious.txt"), out.join("file-link")).unwrap();
+        std::os::unix::fs::symlink(dir.join("missing"), out.join("dangling")).unwrap();
+
+        assert_eq!(
+            prune(&out, &HashSet::new(), &[] as &[&str], false).unwrap(),
+            3
+        );
+
+        for link in ["dir-link", "file-link", "dangling"] {
+            assert!(
+                fs::symlink_metadata(out.join(link)).is_err(),
+                "{link} should be deleted"
+            );
+        }
+        assert!(outside.join("precious.txt").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,271 @@
+//! Redirect pages and server redirect configuration.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    config::{self, Server},
+    html, util, Error, Result,
+};
+
+/// The name of the meta tag pages can use to declare the old paths that
+/// should redirect to them.
+const META_NAME: &str = "redirect_from";
+
+/// Returns the old paths a page declares with a `<meta name="redirect_from">`
+/// tag in its metadata (see [html::metadata]), whose content is a
+/// whitespace-separated list of root-relative paths.
+pub(crate) fn declared(content: &[u8]) -> Vec<String> {
+    let tokens = html::tokenize(&String::from_utf8_lossy(content));
+
+    html::metadata(&tokens)
+        .iter()
+        .filter(|t| t.is_start("meta") && t.attr("name") == Some(META_NAME))
+        .filter_map(|t| t.attr("content"))
+        .flat_map(|paths| paths.split_whitespace().map(str::to_owned))
+        .collect()
+}
+
+/// Returns the file in `out` that serves the root-relative path `from`.
+/// Paths without an extension are treated as directories. Fails if `from` has
+/// `..` components, which could lead outside `out`.
+pub(crate) fn stub_path(from: &str, out: &Path) -> Result<PathBuf> {
+    let relative = from.trim_matches('/');
+
+    if !Path::new(relative)
+        .components()
+        .all(|part| matches!(part, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(Error::RedirectPath(from.to_owned()));
+    }
+
+    let mut res = out.join(relative);
+
+    if from.ends_with('/') || relative.is_empty() || Path::new(relative).extension().is_none() {
+        res.push("index.html");
+    }
+
+    Ok(res)
+}
+
+/// Returns the path of every file [write] writes for `redirects`, with a
+/// description of each (eg ``the redirect from `/old/'``).
+pub(crate) fn paths(
+    config: &config::Config,
+    redirects: &BTreeMap<String, String>,
+) -> Result<Vec<(PathBuf, String)>> {
+    let mut res = Vec::new();
+
+    if redirects.is_empty() {
+        return Ok(res);
+    }
+
+    if config.redirects.stubs {
+        for from in redirects.keys() {
+            res.push((
+                stub_path(from, &config.dirs.out)?,
+                format!("the redirect from `{from}'"),
+            ));
+        }
+    }
+
+    for server in &config.redirects.servers {
+        res.push((
+            config.dirs.out.join(server.file_name()),
+            format!("the {server:?} redirects"),
+        ));
+    }
+
+    Ok(res)
+}
+
+/// Writes an html redirect page for each redirect, plus configuration files
+/// for the servers listed in the config. Returns the paths of the files
+/// written.
+pub(crate) fn write(
+    config: &config::Config,
+    redirects: &BTreeMap<String, String>,
+    dry_run: bool,
+) -> Result<Vec<PathBuf>> {
+    let out = &config.dirs.out;
+    let mut res = Vec::new();
+
+    if config.redirects.stubs {
+        for (from, to) in redirects {
+            let path = stub_path(from, out)?;
+
+            log::info!(
+                "{} redirect '{}' -> `{to}'",
+                util::write_verb(&path, dry_run),
+                path.display()
+            );
+
+            util::write_file(&path, stub(to), dry_run)?;
+            res.push(path);
+        }
+    }
+
+    for server in &config.redirects.servers {
+        let contents = match server {
+            Server::Netlify => netlify(redirects),
+            Server::Nginx => nginx(redirects),
+            Server::Apache => apache(redirects),
+        };
+
+        let path = out.join(server.file_name());
+
+        log::info!(
+            "{} {server:?} redirects to '{}'",
+            util::write_verb(&path, dry_run),
+            path.display()
+        );
+
+        util::write_file(&path, contents, dry_run)?;
+        res.push(path);
+    }
+
+    Ok(res)
+}
+
+impl Server {
+    /// Returns the name of the file redirects for this server are written to,
+    /// in the out directory.
+    fn file_name(self) -> &'static str {
+        match self {
+            Server::Netlify => "_redirects",
+            Server::Nginx => "redirects.nginx.conf",
+            Server::Apache => ".htaccess",
+        }
+    }
+}
+
+/// Returns an html page that redirects to `to`.
+fn stub(to: &str) -> String {
+    let to = util::xml_escape(to);
+
+    format!(
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <title>Redirecting…</title>\n\
+         <link rel=\"canonical\" href=\"{to}\">\n\
+         <meta http-equiv=\"refresh\" content=\"0; url={to}\">\n\
+         <meta name=\"robots\" content=\"noindex\">\n\
+         </head>\n\
+         <body>\n\
+         <p>This page has moved to <a href=\"{to}\">{to}</a>.</p>\n\
+         </body>\n\
+         </html>\n"
+    )
+}
+
+/// Returns a Netlify `_redirects` file.
+fn netlify(redirects: &BTreeMap<String, String>) -> String {
+    let mut res = String::new();
+
+    for (from, to) in redirects {
+        let _ = writeln!(
+            res,
+            "{} {} 301",
+            encode_whitespace(from),
+            encode_whitespace(to)
+        );
+    }
+
+    res
+}
+
+/// Percent-encodes the whitespace in `text`, which would otherwise split a
+/// line of a `_redirects` file into the wrong fields.
+fn encode_whitespace(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if c.is_whitespace() {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                let _ = write!(res, "%{b:02X}");
+            }
+        } else {
+            res.push(c);
+        }
+    }
+
+    res
+}
+
+/// Returns an nginx `map` block, to be included in the `http` context. It
+/// sets `$mksite_redirect` to the new url for each old path, and can be used
+/// like so:
+///
+/// ```nginx
+/// if ($mksite_redirect) {
+///     return 301 $mksite_redirect;
+/// }
+/// ```
+fn nginx(redirects: &BTreeMap<String, String>) -> String {
+    let mut res = String::from("map $uri $mksite_redirect {\n");
+
+    for (from, to) in redirects {
+        let _ = writeln!(res, "    {} {};", nginx_quote(from), nginx_quote(to));
+    }
+
+    res.push_str("}\n");
+    res
+}
+
+/// Quotes a string for use in an nginx config file.
+fn nginx_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns an Apache `.htaccess` file. `RedirectMatch` is used rather than
+/// `Redirect`, which would also redirect every path starting with an old one
+/// (eg `/old-post` for `/old`).
+fn apache(redirects: &BTreeMap<String, String>) -> String {
+    let mut res = String::new();
+
+    for (from, to) in redirects {
+        let _ = writeln!(
+            res,
+            "RedirectMatch 301 {} {}",
+            apache_quote(&apache_pattern(from)),
+            apache_quote(to)
+        );
+    }
+
+    res
+}
+
+/// Returns an anchored regex matching exactly the path `from`. Paths treated
+/// as directories (see [stub_path]) match with or without a trailing slash.
+fn apache_pattern(from: &str) -> String {
+    let trimmed = from.trim_end_matches('/');
+    let is_dir = from.ends_with('/') || Path::new(trimmed).extension().is_none();
+
+    let mut res = String::from("^");
+
+    for c in trimmed.chars() {
+        if r".^$|?*+()[]{}\".contains(c) {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+
+    if is_dir {
+        res.push_str("/?");
+    }
+
+    res.push('$');
+    res
+}
+
+/// Quotes a string for use in an Apache config file. Apache only unescapes
+/// quotes in quoted strings, so backslashes (eg in regexes) are left alone.
+fn apache_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\\\""))
+}
@@ -0,0 +1,86 @@
+// Client-side search for sites built with mksite.
+//
+// Usage:
+//   <script src="/search.js"></script>
+//   <script>
+//     mksiteSearch.load("/search-index.json").then(() => {
+//       const results = mksiteSearch.search("some query");
+//       // => [{ title, url, excerpt, score }, ...]
+//     });
+//   </script>
+(function () {
+  "use strict";
+
+  let index = [];
+
+  function terms(text) {
+    return text.toLowerCase().split(/[^\p{L}\p{N}]+/u).filter(Boolean);
+  }
+
+  function count(haystack, term) {
+    let n = 0;
+    let i = haystack.indexOf(term);
+    while (i !== -1) {
+      n++;
+      i = haystack.indexOf(term, i + term.length);
+    }
+    return n;
+  }
+
+  function excerpt(body, term) {
+    const i = body.toLowerCase().indexOf(term);
+    if (i === -1) return body.slice(0, 160);
+    const start = Math.max(0, i - 60);
+    return (start > 0 ? "…" : "") + body.slice(start, start + 160) + "…";
+  }
+
+  // Every query term must appear somewhere in a page for it to match. Matches
+  // in titles and headings count for more than matches in the body.
+  function search(query, limit = 20) {
+    const query_terms = terms(query);
+    if (query_terms.length === 0) return [];
+
+    const results = [];
+
+    for (const page of index) {
+      const title = page.title.toLowerCase();
+      const headings = page.headings.join(" ").toLowerCase();
+      const body = page.body.toLowerCase();
+
+      let score = 0;
+      let all = true;
+
+      for (const term of query_terms) {
+        const s =
+          10 * count(title, term) + 5 * count(headings, term) + count(body, term);
+        if (s === 0) {
+          all = false;
+          break;
+        }
+        score += s;
+      }
+
+      if (all) {
+        results.push({
+          title: page.title,
+          url: page.url,
+          excerpt: excerpt(page.body, query_terms[0]),
+          score,
+        });
+      }
+    }
+
+    results.sort((a, b) => b.score - a.score);
+    return results.slice(0, limit);
+  }
+
+  function load(url) {
+    return fetch(url)
+      .then((response) => response.json())
+      .then((data) => {
+        index = data;
+      });
+  }
+
+  window.mksiteSearch = { load, search };
+})();
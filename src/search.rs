@@ -0,0 +1,101 @@
+//! Client-side search index generation.
+
+use std::path::{Path, PathBuf};
+
+use crate::{config, html, util, Result};
+
+/// The script written alongside the search index, which loads and queries it
+/// in the browser.
+const SCRIPT: &str = include_str!("search.js");
+
+/// A page in the search index.
+#[derive(serde::Serialize)]
+struct Entry {
+    /// The title of the page.
+    title: String,
+
+    /// The root-relative url of the page.
+    url: String,
+
+    /// The text of every heading on the page.
+    headings: Vec<String>,
+
+    /// The text of the page, without any markup.
+    body: String,
+}
+
+impl Entry {
+    /// Creates an index entry for the page written to `destination` with the
+    /// given `content`. The body text is taken from the page's `<main>`
+    /// element if it has one, then from `<body>`, so that layout boilerplate
+    /// is mostly left out.
+    fn new(destination: &Path, content: &[u8], out: &Path) -> Result<Self> {
+        let content = String::from_utf8_lossy(content);
+        let tokens = html::tokenize(&content);
+
+        let main = html::element(&tokens, "main")
+            .or_else(|| html::element(&tokens, "body"))
+            .unwrap_or(&tokens);
+
+        let url = util::page_url(destination, out)?;
+
+        Ok(Self {
+            title: html::element_text(&tokens, "title")
+                .filter(|t| !t.is_empty())
+                .or_else(|| html::element_text(main, "h1"))
+                .unwrap_or_else(|| url.clone()),
+            url,
+            headings: html::headings(main),
+            body: html::text(main),
+        })
+    }
+}
+
+/// Writes the search index for the given output pages and their contents, and
+/// the script to query it, to the paths configured in `config.search`. Returns
+/// the paths of the files written.
+pub(crate) fn write(
+    config: &config::Config,
+    pages: &[(&Path, &[u8])],
+    dry_run: bool,
+) -> Result<Vec<PathBuf>> {
+    let include = util::glob_set(&config.search.include)?;
+    let exclude = util::glob_set(&config.search.exclude)?;
+
+    let mut entries = Vec::new();
+
+    for (page, content) in pages {
+        if (config.search.include.is_empty() || include.is_match(page)) && !exclude.is_match(page) {
+            log::debug!("Indexing '{}'", page.display());
+            entries.push(Entry::new(page, content, &config.dirs.out)?);
+        }
+    }
+
+    let path = config.dirs.out.join(&config.search.path);
+
+    log::info!(
+        "{} search index '{}' with {} page{}",
+        util::write_verb(&path, dry_run),
+        path.display(),
+        entries.len(),
+        if entries.len() != 1 { "s" } else { "" }
+    );
+
+    util::write_file(
+        &path,
+        serde_json::to_string(&entries).expect("Serializing the search index should be infallible"),
+        dry_run,
+    )?;
+
+    let script = config.dirs.out.join(&config.search.script);
+
+    log::info!(
+        "{} search script '{}'",
+        util::write_verb(&script, dry_run),
+        script.display()
+    );
+
+    util::write_file(&script, SCRIPT, dry_run)?;
+
+    Ok(vec![path, script])
+}
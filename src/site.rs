@@ -1,8 +1,47 @@
 //! Types and methods for modeling and building the website.
 
-use std::{ffi::OsStr, fs, path::PathBuf};
-
-use crate::{config, transform, util, Error, Result};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ffi::OsStr,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    assets, config, env, feed, html, images, links, permalink, prune, redirect, search, sitemap,
+    transform, util, Error, Result,
+};
+
+/// The name of directories holding draft pages. Draft pages are written as if
+/// they were in the parent directory.
+const DRAFTS_DIR: &str = "_drafts";
+
+/// Options for a single build of the site, usually from the command line.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Options {
+    /// Whether to build draft pages, which are skipped otherwise.
+    pub(crate) drafts: bool,
+
+    /// Whether to build pages with a publication date after `now`, which are
+    /// skipped otherwise.
+    pub(crate) future: bool,
+
+    /// The time to compare publication dates against. Defaults to the current
+    /// time.
+    pub(crate) now: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Whether to only work out what a build would do, without writing any
+    /// files (including images processed while rendering).
+    pub(crate) dry_run: bool,
+
+    /// Whether to run transforms in a dry run, which are skipped otherwise.
+    pub(crate) run_transforms: bool,
+
+    /// Whether to delete stale files from the out directory after building,
+    /// even if `prune.enable` is not set.
+    pub(crate) prune: bool,
+}
 
 /// Structure representing the site as a whole, containing all the pages and
 /// layouts, the site configuration, and the templating engine.
@@ -10,6 +49,10 @@ pub(crate) struct Site {
     /// The site configuration defined in the `mksite.toml` file.
     config: config::Config,
 
+    /// The image processor used by templates, which keeps track of the images
+    /// it produces.
+    images: images::Processor,
+
     /// The rendering engine for all templating and layouts.
     tera: tera::Tera,
 
@@ -21,37 +64,140 @@ pub(crate) struct Site {
 
     /// List of mappings from sources to outputs.
     mappings: Vec<Mapping>,
+
+    /// The paths of all the static assets, if the static directory exists.
+    statics: Vec<PathBuf>,
+
+    /// Where each static asset is copied to, fingerprinted or not.
+    manifest: assets::Manifest,
+
+    /// Whether transforms have been applied to the content of the mappings.
+    transformed: bool,
+
+    /// The options for this build.
+    options: Options,
 }
 
 impl Site {
     /// Constructs a new site using the information in the given config.
-    pub fn new(config: config::Config) -> Result<Self> {
+    pub fn new(config: config::Config, options: Options) -> Result<Self> {
         Ok(Self {
             config: config.clone(),
+            images: images::Processor::new(&config, options.dry_run),
             tera: tera::Tera::default(),
             sources: util::walk_dir(&config.dirs.src)?,
             mappings: Vec::new(),
+            statics: if config.dirs.r#static.exists() {
+                util::walk_dir(&config.dirs.r#static)?
+            } else {
+                Vec::new()
+            },
             layouts: if config.dirs.layout.exists() {
                 Some(util::walk_dir(&config.dirs.layout)?)
             } else {
                 None
             },
+            manifest: assets::Manifest::build(&config.dirs.r#static, &config.fingerprint)?,
+            transformed: false,
+            options,
         })
     }
 
     /// Builds templates, renders them, applies transforms and layouts, and
-    /// copies the results to the configured output directory.
-    pub fn build(mut self) -> Result<()> {
+    /// copies the results to the configured output directory, then deletes
+    /// stale files from it if pruning is enabled. In a dry run, what would be
+    /// written or deleted is logged instead.
+    pub fn build(&mut self) -> Result<()> {
         self.build_templates()?;
         let rendered_pages = self.render_pages()?;
         self.prepare_mappings(rendered_pages)?;
+        self.resolve_collisions(true)?;
         self.apply_transforms()?;
-        self.apply_layouts_and_write_output()?;
-        self.copy_statics()
+        // redirects declared in transformed pages are only known now
+        self.resolve_collisions(true)?;
+        self.reserve_outputs()?;
+        let outputs = self.apply_layouts_and_write_output()?;
+
+        let mut written = self
+            .mappings
+            .iter()
+            .map(|m| m.destination.clone())
+            .collect::<HashSet<_>>();
+        written.extend(self.write_sitemap()?);
+        written.extend(self.write_feeds()?);
+        written.extend(self.write_search_index(&outputs)?);
+        written.extend(self.write_redirects()?);
+        written.extend(self.copy_statics()?);
+        written.extend(self.images.outputs());
+
+        if self.options.prune || self.config.prune.enable {
+            prune::prune(
+                &self.config.dirs.out,
+                &written,
+                &self.config.prune.keep,
+                self.options.dry_run,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Renders every page and works out where it would be written, with which
+    /// transform and layout, along with where every static asset would be
+    /// copied, without applying transforms or writing anything. Routes are
+    /// sorted by destination.
+    pub fn routes(&mut self) -> Result<Vec<Route>> {
+        self.build_templates()?;
+        let rendered_pages = self.render_pages()?;
+        self.prepare_mappings(rendered_pages)?;
+        self.resolve_collisions(false)?;
+
+        let mut res = Vec::new();
+
+        for mapping in &self.mappings {
+            res.push(Route {
+                kind: RouteKind::Page,
+                source: mapping.source.clone(),
+                destination: mapping.destination.clone(),
+                transform: mapping.transform.clone(),
+                layout: self.find_layout(mapping)?,
+            });
+        }
+
+        for asset in &self.statics {
+            res.push(Route {
+                kind: RouteKind::Static,
+                destination: self.static_destination(asset)?,
+                source: asset.to_owned(),
+                transform: None,
+                layout: None,
+            });
+        }
+
+        res.sort_by(|a, b| (&a.destination, &a.source).cmp(&(&b.destination, &b.source)));
+
+        Ok(res)
     }
 
     /// Builds (but does not render) Tera templates for the site.
     fn build_templates(&mut self) -> Result<()> {
+        self.tera.register_function(
+            "asset_url",
+            assets::asset_url(
+                self.manifest.clone(),
+                self.config.fingerprint.prefix.clone(),
+            ),
+        );
+        self.tera
+            .register_function("sri", assets::sri(self.config.dirs.clone()));
+
+        self.tera
+            .register_function("resize_image", images::resize_image(self.images.clone()));
+        self.tera
+            .register_function("srcset", images::srcset(self.images.clone()));
+        self.tera
+            .register_function("env", env::function(self.config.env.allow.clone()));
+
         // build page templates
         let dir = &self.config.dirs.src;
 
@@ -85,6 +231,7 @@ impl Site {
     fn render_pages(&mut self) -> Result<Vec<(PathBuf, Vec<u8>)>> {
         let mut context = tera::Context::new();
         context.insert("data", &self.config.data);
+        context.insert("profile", &self.config.profile);
 
         // render page contents
         self.render_page_templates(context)
@@ -92,67 +239,388 @@ impl Site {
 
     /// Prepares the mappings required for each [Page] based on transform configurations.
     fn prepare_mappings(&mut self, rendered_pages: Vec<(PathBuf, Vec<u8>)>) -> Result<()> {
+        let now = self.options.now.unwrap_or_else(chrono::Utc::now);
+
         for (source, content) in rendered_pages {
-            let mut destination =
-                util::swap_prefix(&source, &self.config.dirs.src, &self.config.dirs.out)?;
+            let tokens = html::tokenize(&String::from_utf8_lossy(&content));
 
-            if self.config.ignores.transform.contains(&destination) {
-                log::info!(
-                    "Skipping transform step for '{}' as it is in the transform ignore list",
-                    destination.display()
-                );
+            if !self.options.drafts && self.is_draft(&source, &tokens)? {
+                log::info!("Skipping draft '{}'", source.display());
+                continue;
+            }
+
+            if let Some(date) = util::page_date(&source, &tokens) {
+                if !self.options.future && date > now {
+                    log::info!(
+                        "Skipping '{}' as it is scheduled for {}",
+                        source.display(),
+                        date.to_rfc3339()
+                    );
+                    continue;
+                }
+            }
+
+            for (mirrored, transform) in self.destinations(&source)? {
+                self.mappings.push(Mapping {
+                    source: source.to_owned(),
+                    destination: self.output_path(&source, &mirrored)?,
+                    mirrored,
+                    transform,
+                    content: content.to_owned(),
+                });
             }
+        }
+
+        log::debug!(
+            "Mapped {} page{}",
+            self.mappings.len(),
+            if self.mappings.len() != 1 { "s" } else { "" }
+        );
+
+        Ok(())
+    }
+
+    /// Finds every path that several pages or static assets are written to,
+    /// and settles which one wins using `collisions.precedence`: the source
+    /// matching the earliest entry, as long as no other source matches it too.
+    /// The other sources are not written to that path.
+    ///
+    /// Files the build generates itself (see [Site::generated]) can't be
+    /// replaced, so any collision involving one is never settled.
+    ///
+    /// Collisions that can't be settled are logged, and are errors if `strict`
+    /// is set. Otherwise, all the sources involved are kept.
+    fn resolve_collisions(&mut self, strict: bool) -> Result<()> {
+        let mut writers = BTreeMap::<PathBuf, Vec<PathBuf>>::new();
+
+        for mapping in &self.mappings {
+            writers
+                .entry(mapping.destination.clone())
+                .or_default()
+                .push(mapping.source.clone());
+        }
+
+        for asset in &self.statics {
+            writers
+                .entry(self.static_destination(asset)?)
+                .or_default()
+                .push(asset.clone());
+        }
+
+        let mut generated = BTreeMap::<PathBuf, Vec<String>>::new();
+
+        for (path, description) in self.generated()? {
+            generated.entry(path).or_default().push(description);
+        }
 
-            match source.extension().and_then(OsStr::to_str) {
-                Some(ext)
-                    if self.config.transforms.contains_key(ext)
-                        && !self.config.ignores.transform.contains(&destination) =>
+        let precedence = self
+            .config
+            .collisions
+            .precedence
+            .iter()
+            .map(|pattern| util::glob_set(&[pattern]))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut losers = Vec::new();
+        let mut unsettled = 0;
+
+        let destinations = writers
+            .keys()
+            .chain(generated.keys())
+            .collect::<BTreeSet<_>>();
+
+        for destination in destinations {
+            let sources = writers.get(destination).map_or(&[][..], Vec::as_slice);
+            let files = generated.get(destination).map_or(&[][..], Vec::as_slice);
+
+            if sources.len() + files.len() < 2 {
+                continue;
+            }
+
+            let ranks = sources
+                .iter()
+                .map(|source| precedence.iter().position(|glob| glob.is_match(source)))
+                .collect::<Vec<_>>();
+
+            let best = ranks.iter().flatten().min();
+            let winner = match best {
+                Some(best)
+                    if files.is_empty()
+                        && ranks.iter().filter(|r| r.as_ref() == Some(best)).count() == 1 =>
                 {
-                    log::debug!("Transforms apply to source '{}'", source.display());
-
-                    for (target_ext, transform) in &self.config.transforms[ext] {
-                        destination.set_extension(target_ext);
-
-                        log::debug!(
-                            "Mapping '{}' -> '{}' via {transform}",
-                            source.display(),
-                            destination.display()
-                        );
-
-                        self.mappings.push(Mapping {
-                            source: source.to_owned(),
-                            destination: destination.clone(),
-                            transform: Some(transform.to_owned()),
-                            content: content.to_owned(),
-                        })
+                    ranks.iter().position(|r| r.as_ref() == Some(best))
+                }
+                _ => None,
+            };
+
+            let listed = sources
+                .iter()
+                .map(|source| format!("'{}'", source.display()))
+                .chain(files.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            match winner {
+                Some(winner) => {
+                    log::info!(
+                        "'{}' is written by each of {listed}, using '{}' as it comes first in `collisions.precedence'",
+                        destination.display(),
+                        sources[winner].display()
+                    );
+
+                    for (i, source) in sources.iter().enumerate() {
+                        if i != winner {
+                            losers.push((source.clone(), destination.clone()));
+                        }
                     }
                 }
-                _ => {
-                    log::debug!("No transforms apply to source 'src/index.md'");
+                None => {
+                    log::log!(
+                        if strict {
+                            log::Level::Error
+                        } else {
+                            log::Level::Warn
+                        },
+                        "'{}' is written by each of {listed}",
+                        destination.display()
+                    );
+
+                    unsettled += 1;
+                }
+            }
+        }
+
+        if strict && unsettled > 0 {
+            return Err(Error::Collisions(unsettled));
+        }
+
+        self.mappings.retain(|m| {
+            !losers
+                .iter()
+                .any(|(source, destination)| *source == m.source && *destination == m.destination)
+        });
+        self.statics
+            .retain(|asset| !losers.iter().any(|(source, _)| source == asset));
+
+        Ok(())
+    }
+
+    /// Tells the image processor about every other file the build writes, so
+    /// images resized from layouts, which are only processed as pages are
+    /// written, can't overwrite them.
+    fn reserve_outputs(&self) -> Result<()> {
+        let mut paths = self
+            .mappings
+            .iter()
+            .map(|m| m.destination.clone())
+            .collect::<HashSet<_>>();
+
+        for asset in &self.statics {
+            paths.insert(self.static_destination(asset)?);
+        }
+
+        let images = self.images.outputs();
+        paths.extend(
+            self.generated()?
+                .into_iter()
+                .map(|(path, _)| path)
+                .filter(|path| !images.contains(path)),
+        );
+
+        self.images.reserve(paths);
+
+        Ok(())
+    }
+
+    /// Returns the path of every file the build generates itself, rather than
+    /// from a single page or static asset, with a description of each (eg
+    /// `the sitemap`).
+    fn generated(&self) -> Result<Vec<(PathBuf, String)>> {
+        let out = &self.config.dirs.out;
+        let mut res = Vec::new();
+
+        if self.config.sitemap.enable {
+            for path in sitemap::paths(&self.config, self.sitemap_entries()?.len()) {
+                res.push((path, "the sitemap".to_owned()));
+            }
+        }
+
+        for feed in &self.config.feeds {
+            res.push((out.join(&feed.path), format!("the feed `{}'", feed.title)));
+        }
+
+        if self.config.search.enable {
+            res.push((
+                out.join(&self.config.search.path),
+                "the search index".to_owned(),
+            ));
+            res.push((
+                out.join(&self.config.search.script),
+                "the search script".to_owned(),
+            ));
+        }
+
+        if self.config.fingerprint.enable {
+            res.push((
+                out.join(&self.config.fingerprint.manifest),
+                "the asset manifest".to_owned(),
+            ));
+        }
+
+        res.extend(redirect::paths(&self.config, &self.redirects()?)?);
+
+        for image in self.images.outputs() {
+            res.push((image, "a processed image".to_owned()));
+        }
+
+        Ok(res)
+    }
+
+    /// Returns true if `source` is a draft, ie it is in a `_drafts` directory
+    /// or the metadata in its rendered `tokens` (see [html::metadata])
+    /// includes a `<meta name="draft">` tag. The tag's `content` can be set to
+    /// `false` to publish a page without removing it.
+    fn is_draft(&self, source: &Path, tokens: &[html::Token]) -> Result<bool> {
+        let relative = util::swap_prefix(source, &self.config.dirs.src, "")?;
+
+        if relative.iter().any(|part| part == DRAFTS_DIR) {
+            return Ok(true);
+        }
+
+        Ok(html::metadata(tokens)
+            .iter()
+            .find(|t| t.is_start("meta") && t.attr("name") == Some("draft"))
+            .is_some_and(|t| t.attr("content") != Some("false")))
+    }
+
+    /// Returns the path of every page generated from `source`, mirroring the
+    /// source path in the out directory, along with the transform that
+    /// generates it, if any. Pages in `_drafts` directories are mirrored as if
+    /// they were in the parent directory. This only depends on the source path
+    /// and the config, so it can be used without rendering anything.
+    ///
+    /// See [Site::output_path] for where these pages are actually written.
+    fn destinations(&self, source: &Path) -> Result<Vec<(PathBuf, Option<transform::Transform>)>> {
+        let mut res = Vec::new();
+
+        let relative = util::swap_prefix(source, &self.config.dirs.src, "")?;
+        let mut destination = self.config.dirs.out.join(
+            relative
+                .iter()
+                .filter(|part| *part != DRAFTS_DIR)
+                .collect::<PathBuf>(),
+        );
+
+        if self.config.ignores.transform.contains(&destination) {
+            log::info!(
+                "Skipping transform step for '{}' as it is in the transform ignore list",
+                destination.display()
+            );
+        }
+
+        match source.extension().and_then(OsStr::to_str) {
+            Some(ext)
+                if self.config.transforms.contains_key(ext)
+                    && !self.config.ignores.transform.contains(&destination) =>
+            {
+                log::debug!("Transforms apply to source '{}'", source.display());
+
+                for (target_ext, transform) in &self.config.transforms[ext] {
+                    destination.set_extension(target_ext);
 
                     log::debug!(
-                        "Mapping '{}' -> '{}'",
+                        "Mapping '{}' -> '{}' via {transform}",
                         source.display(),
                         destination.display()
                     );
 
-                    self.mappings.push(Mapping {
-                        source: source.to_owned(),
-                        destination,
-                        transform: None,
-                        content,
-                    });
+                    res.push((destination.clone(), Some(transform.to_owned())));
                 }
             }
+            _ => {
+                log::debug!("No transforms apply to source '{}'", source.display());
+
+                log::debug!(
+                    "Mapping '{}' -> '{}'",
+                    source.display(),
+                    destination.display()
+                );
+
+                res.push((destination, None));
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Returns the path a page generated from `source` is written to, given the
+    /// path that mirrors the source path in the out directory. This is decided
+    /// by the first of these that applies:
+    ///
+    /// - A matching pattern in `permalinks`.
+    /// - Pretty urls, which turn `out/about.html` into `out/about/index.html`.
+    /// - The mirrored path itself.
+    fn output_path(&self, source: &Path, mirrored: &Path) -> Result<PathBuf> {
+        if let Some((_, pattern)) = self.permalink(source)? {
+            let res = permalink::expand(pattern, source, mirrored, &self.config.dirs.out)?;
+
+            log::debug!(
+                "Using permalink '{}' for '{}'",
+                res.display(),
+                mirrored.display()
+            );
+
+            return Ok(res);
+        }
+
+        let pretty = &self.config.pretty_urls;
+
+        let is_html = matches!(
+            mirrored.extension().and_then(OsStr::to_str),
+            Some("html" | "htm")
+        );
+        let is_index = mirrored.file_stem() == Some(OsStr::new("index"));
+
+        if !is_html || is_index {
+            return Ok(mirrored.to_owned());
+        }
+
+        let enabled = (pretty.enable || util::glob_set(&pretty.include)?.is_match(source))
+            && !util::glob_set(&pretty.exclude)?.is_match(source);
+
+        if !enabled {
+            return Ok(mirrored.to_owned());
+        }
+
+        let mut res = mirrored.with_extension("");
+        res.push("index");
+        if let Some(ext) = mirrored.extension() {
+            res.set_extension(ext);
         }
 
         log::debug!(
-            "Mapped {} page{}",
-            self.mappings.len(),
-            if self.mappings.len() != 1 { "s" } else { "" }
+            "Using pretty url '{}' for '{}'",
+            res.display(),
+            mirrored.display()
         );
 
-        Ok(())
+        Ok(res)
+    }
+
+    /// Returns the glob and pattern in `permalinks` that apply to `source`, if
+    /// any. If several globs match, the longest one wins.
+    fn permalink(&self, source: &Path) -> Result<Option<(&String, &String)>> {
+        let mut res: Option<(&String, &String)> = None;
+
+        for (glob, pattern) in &self.config.permalinks {
+            if util::glob_set(&[glob])?.is_match(source)
+                && res.is_none_or(|(longest, _)| (glob.len(), glob) > (longest.len(), longest))
+            {
+                res = Some((glob, pattern));
+            }
+        }
+
+        Ok(res)
     }
 
     /// Renders all page templates and returns their contents as byte vecs
@@ -199,8 +667,11 @@ impl Site {
         Ok(res)
     }
 
-    /// Apply layouts and write the generated files.
-    fn apply_layouts_and_write_output(&self) -> Result<()> {
+    /// Apply layouts and write the generated files. Returns the final contents
+    /// of each mapping's page, in the same order as the mappings.
+    fn apply_layouts_and_write_output(&self) -> Result<Vec<Vec<u8>>> {
+        let mut res = Vec::new();
+
         for mapping in &self.mappings {
             let layout = self.find_layout(mapping)?;
 
@@ -219,6 +690,7 @@ impl Site {
 
                     let mut context = tera::Context::new();
                     context.insert("data", &self.config.data);
+                    context.insert("profile", &self.config.profile);
 
                     log::debug!(
                         "NOTE: Context field `page.content` is omitted from debug output\nUsing layout rendering {context:#?}"
@@ -248,27 +720,23 @@ impl Site {
                 }
             };
 
-            log::info!("Writing '{}'", mapping.destination.display());
+            log::info!(
+                "{} '{}'",
+                util::write_verb(&mapping.destination, self.options.dry_run),
+                mapping.destination.display()
+            );
 
-            if let Some(p) = mapping.destination.parent() {
-                fs::create_dir_all(p).map_err(|source| Error::Io {
-                    msg: format!("Cannot create '{}'", p.display()),
-                    source,
-                })?;
-            }
+            util::write_file(&mapping.destination, &output, self.options.dry_run)?;
 
-            fs::write(&mapping.destination, output).map_err(|source| Error::Io {
-                msg: format!("Cannot write '{}'", mapping.destination.display()),
-                source,
-            })?;
+            res.push(output);
         }
 
-        Ok(())
+        Ok(res)
     }
 
     /// Returns the path to the applicable layout for a Mapping, if one exists.
     fn find_layout(&self, mapping: &Mapping) -> Result<Option<PathBuf>> {
-        if self.config.ignores.layout.contains(&mapping.destination) {
+        if self.is_layout_ignored(&mapping.destination, &mapping.mirrored) {
             log::info!(
                 "Skipping layout for '{}' as it is in the layout ignore list",
                 mapping.destination.display()
@@ -290,48 +758,12 @@ impl Site {
 
             Some(layouts) => {
                 // if there is a layout folder, look for an applicable layout
+                for candidate in self.layout_candidates(&mapping.mirrored)? {
+                    log::debug!("Searching for layout '{}'", candidate.display());
 
-                // start with the corresponding path
-                let layout_path = util::swap_prefix(
-                    &mapping.destination,
-                    &self.config.dirs.out,
-                    &self.config.dirs.layout,
-                )?;
-
-                // if that doesn't exist we'd better go looking for it
-                if !layouts.contains(&layout_path) {
-                    log::debug!(
-                        "Exact layout match for '{}' not found",
-                        mapping.destination.display()
-                    );
-
-                    // all this work to concatenate a file extension with an
-                    // underscore :/
-                    let wildcard = "_".to_owned()
-                        + &match layout_path.extension() {
-                            None => "".to_owned(),
-                            Some(ext) => {
-                                if let Some(ext) = ext.to_str() {
-                                    ".".to_owned() + ext
-                                } else {
-                                    "".to_owned()
-                                }
-                            }
-                        };
-
-                    // iterate up the directory tree until we find a matching
-                    // wildcard layout
-                    for ancestor in layout_path.ancestors() {
-                        log::debug!(
-                            "Searching for wildcard layout '{}' in '{}/'",
-                            wildcard,
-                            ancestor.display()
-                        );
-                        let layout_path = ancestor.join(&wildcard);
-                        if layouts.contains(&layout_path) {
-                            log::debug!("Found layout '{}'", layout_path.display());
-                            return Ok(Some(layout_path));
-                        }
+                    if layouts.contains(&candidate) {
+                        log::debug!("Found layout '{}'", candidate.display());
+                        return Ok(Some(candidate));
                     }
                 }
 
@@ -341,20 +773,416 @@ impl Site {
         }
     }
 
-    /// Applies the transforms for every mapping, mutating them.
+    /// Returns true if the page at `destination`, mirrored at `mirrored`, is
+    /// in the layout ignore list.
+    fn is_layout_ignored(&self, destination: &Path, mirrored: &Path) -> bool {
+        self.config
+            .ignores
+            .layout
+            .iter()
+            .any(|p| p == destination || p == mirrored)
+    }
+
+    /// Returns the layouts that could apply to the page mirrored at `mirrored`,
+    /// in the order they are tried: the layout at the corresponding path, then
+    /// the wildcard layout (eg `_.html`) in each directory from there up to the
+    /// layout directory.
+    fn layout_candidates(&self, mirrored: &Path) -> Result<Vec<PathBuf>> {
+        // start with the corresponding path. this uses the mirrored path so
+        // that pretty urls don't change which layouts apply
+        let layout_path =
+            util::swap_prefix(mirrored, &self.config.dirs.out, &self.config.dirs.layout)?;
+
+        // all this work to concatenate a file extension with an underscore :/
+        let wildcard = "_".to_owned()
+            + &match layout_path.extension() {
+                None => "".to_owned(),
+                Some(ext) => {
+                    if let Some(ext) = ext.to_str() {
+                        ".".to_owned() + ext
+                    } else {
+                        "".to_owned()
+                    }
+                }
+            };
+
+        let mut res = vec![layout_path.clone()];
+
+        // iterate up the directory tree for wildcard layouts
+        for ancestor in layout_path
+            .ancestors()
+            .skip(1)
+            .take_while(|a| a.starts_with(&self.config.dirs.layout))
+        {
+            res.push(ancestor.join(&wildcard));
+        }
+
+        Ok(res)
+    }
+
+    /// Applies the transforms for every mapping, mutating them. In a dry run,
+    /// transforms are only run if [Options::run_transforms] is set.
     fn apply_transforms(&mut self) -> Result<()> {
+        let skip = self.skips_transforms();
+
         for mapping in &mut self.mappings {
-            mapping.transform()?;
+            match &mapping.transform {
+                Some(transform) if skip => {
+                    log::info!(
+                        "Would apply transform {transform} to '{}'",
+                        mapping.destination.display()
+                    );
+                }
+                _ => mapping.transform()?,
+            }
+        }
+
+        self.transformed = !skip;
+
+        Ok(())
+    }
+
+    /// Returns true if transforms are not run, ie in a dry run without
+    /// [Options::run_transforms].
+    fn skips_transforms(&self) -> bool {
+        self.options.dry_run && !self.options.run_transforms
+    }
+
+    /// Returns true if `mapping` has a transform that has not been applied,
+    /// either yet or at all in a dry run, so its content is not what would be
+    /// written.
+    fn pending_transform(&self, mapping: &Mapping) -> bool {
+        mapping.transform.is_some() && !self.transformed
+    }
+
+    /// Writes a sitemap listing every html page, if enabled in the config.
+    /// Returns the paths of the files written.
+    fn write_sitemap(&self) -> Result<Vec<PathBuf>> {
+        if !self.config.sitemap.enable {
+            return Ok(Vec::new());
+        }
+
+        sitemap::write(&self.config, &self.sitemap_entries()?, self.options.dry_run)
+    }
+
+    /// Returns the sitemap entry for every html page, sorted and deduplicated
+    /// by url.
+    fn sitemap_entries(&self) -> Result<Vec<sitemap::Entry>> {
+        let mut entries = Vec::new();
+
+        for mapping in &self.mappings {
+            if !mapping.is_html() {
+                continue;
+            }
+
+            entries.push(sitemap::Entry {
+                url: util::page_url(&mapping.destination, &self.config.dirs.out)?,
+                modified: fs::metadata(&mapping.source)
+                    .and_then(|m| m.modified())
+                    .ok(),
+            });
+        }
+
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+        entries.dedup_by(|a, b| a.url == b.url);
+
+        Ok(entries)
+    }
+
+    /// Writes every feed defined in the config, using the content of each
+    /// matching page as it was after the transform step. Returns the paths of
+    /// the files written.
+    fn write_feeds(&self) -> Result<Vec<PathBuf>> {
+        let mut res = Vec::new();
+
+        for feed in &self.config.feeds {
+            let sources = util::glob_set(&[&feed.source])?;
+            let mut items = Vec::new();
+
+            for mapping in &self.mappings {
+                if sources.is_match(&mapping.source) && mapping.is_html() {
+                    items.push(feed::Item::new(
+                        &mapping.source,
+                        &mapping.destination,
+                        &mapping.content,
+                        &self.config.dirs.out,
+                        feed.content,
+                    )?);
+                }
+            }
+
+            res.push(feed::write(
+                &self.config,
+                feed,
+                items,
+                self.options.dry_run,
+            )?);
+        }
+
+        Ok(res)
+    }
+
+    /// Writes a search index of every html page, if enabled in the config,
+    /// given the final contents of each mapping's page. Returns the paths of
+    /// the files written.
+    fn write_search_index(&self, outputs: &[Vec<u8>]) -> Result<Vec<PathBuf>> {
+        if !self.config.search.enable {
+            return Ok(Vec::new());
+        }
+
+        let mut pages = self
+            .mappings
+            .iter()
+            .zip(outputs)
+            .filter(|(m, _)| m.is_html())
+            .map(|(m, output)| (m.destination.as_path(), output.as_slice()))
+            .collect::<Vec<_>>();
+
+        // like the files themselves, the last page written to a path wins
+        pages.reverse();
+        pages.sort_by_key(|(destination, _)| *destination);
+        pages.dedup_by_key(|(destination, _)| *destination);
+
+        search::write(&self.config, &pages, self.options.dry_run)
+    }
+
+    /// Writes redirects declared in the config and in pages' `redirect_from`
+    /// meta tags.
+    fn write_redirects(&self) -> Result<Vec<PathBuf>> {
+        let redirects = self.redirects()?;
+
+        // only pages that look like they declare redirects are worth a warning
+        let untransformed = self
+            .mappings
+            .iter()
+            .filter(|m| {
+                m.is_html()
+                    && self.pending_transform(m)
+                    && !redirect::declared(&m.content).is_empty()
+            })
+            .count();
+
+        if untransformed > 0 {
+            log::warn!(
+                "Not detecting redirects declared in {untransformed} transformed page{}, as transforms are not run; use `--run-transforms' to include them",
+                if untransformed != 1 { "s" } else { "" }
+            );
+        }
+
+        if redirects.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        redirect::write(&self.config, &redirects, self.options.dry_run)
+    }
+
+    /// Returns every redirect declared in the config and in pages'
+    /// `redirect_from` meta tags, as a map from old paths to new urls. Pages
+    /// whose transform is pending (see [Site::pending_transform]) are left out,
+    /// as their meta tags may not survive the transform.
+    fn redirects(&self) -> Result<BTreeMap<String, String>> {
+        let mut redirects = self
+            .config
+            .redirects
+            .paths
+            .iter()
+            .map(|(from, to)| (from.to_owned(), to.to_owned()))
+            .collect::<BTreeMap<_, _>>();
+
+        for mapping in self
+            .mappings
+            .iter()
+            .filter(|m| m.is_html() && !self.pending_transform(m))
+        {
+            for from in redirect::declared(&mapping.content) {
+                redirects.insert(
+                    from,
+                    util::page_url(&mapping.destination, &self.config.dirs.out)?,
+                );
+            }
+        }
+
+        Ok(redirects)
+    }
+
+    /// Returns the path a static asset is copied to, taking fingerprinting
+    /// into account.
+    fn static_destination(&self, asset: &Path) -> Result<PathBuf> {
+        let logical = util::swap_prefix(asset, &self.config.dirs.r#static, "")?;
+
+        Ok(match self.manifest.get(&util::to_url_path(&logical)?) {
+            Some(output) => self.config.dirs.out.join(output),
+            None => self.config.dirs.out.join(logical),
+        })
+    }
+
+    /// Returns a map from every file a build writes to the source page or
+    /// static asset it comes from.
+    fn origins(&self) -> Result<HashMap<PathBuf, PathBuf>> {
+        let mut res = HashMap::new();
+
+        for source in &self.sources {
+            for (mirrored, _) in self.destinations(source)? {
+                res.insert(self.output_path(source, &mirrored)?, source.to_owned());
+            }
+        }
+
+        for asset in &self.statics {
+            res.insert(self.static_destination(asset)?, asset.to_owned());
+        }
+
+        Ok(res)
+    }
+
+    /// Checks the links in every html page in the out directory, failing if
+    /// any of them are broken. Links to other sites are only checked if
+    /// `external` is true.
+    pub fn check_links(&self, external: bool) -> Result<()> {
+        log::info!("Checking links in '{}/'", self.config.dirs.out.display());
+
+        let broken = links::check(&self.config, &self.origins()?, external)?;
+
+        if broken > 0 {
+            return Err(Error::BrokenLinks(broken));
         }
 
+        log::info!("No broken links found");
+
         Ok(())
     }
 
-    /// Copies the contents of the static dir to the output dir.
-    fn copy_statics(&self) -> Result<()> {
-        for asset in util::walk_dir(&self.config.dirs.r#static)? {
-            let destination =
-                util::swap_prefix(&asset, &self.config.dirs.r#static, &self.config.dirs.out)?;
+    /// Describes how the file at `path` is built, where `path` is a source
+    /// page, a static asset, or a file in the out directory. For pages, this
+    /// covers the ignore lists that apply, the transforms the page goes
+    /// through, where each output is written, and every layout tried for it.
+    pub fn explain(&self, path: &Path) -> Result<String> {
+        let is_static = path.starts_with(&self.config.dirs.r#static) && path.is_file();
+
+        let source = if is_static || self.sources.iter().any(|s| s == path) {
+            path.to_owned()
+        } else {
+            let mut origins = self.origins()?;
+
+            match origins
+                .remove(path)
+                .or_else(|| origins.remove(&path.join("index.html")))
+            {
+                Some(source) => source,
+                None => return Err(Error::UnknownPath(path.to_owned())),
+            }
+        };
+
+        let mut res = String::new();
+
+        if source.starts_with(&self.config.dirs.r#static) {
+            let _ = writeln!(
+                res,
+                "Static asset '{}' is copied to '{}'",
+                source.display(),
+                self.static_destination(&source)?.display()
+            );
+
+            return Ok(res);
+        }
+
+        let _ = writeln!(res, "Source '{}'", source.display());
+
+        if self.config.ignores.template.contains(&source) {
+            let _ = writeln!(
+                res,
+                "  Not rendered with Tera, as it is in `ignores.template'"
+            );
+        } else {
+            let _ = writeln!(res, "  Rendered with Tera");
+        }
+
+        for (mirrored, transform) in self.destinations(&source)? {
+            let destination = self.output_path(&source, &mirrored)?;
+
+            let _ = writeln!(res, "\nOutput '{}'", destination.display());
+
+            match transform {
+                Some(transform) => {
+                    let _ = writeln!(res, "  Transformed with {transform}");
+                }
+                None if self.config.ignores.transform.contains(&mirrored) => {
+                    let _ = writeln!(res, "  Not transformed, as it is in `ignores.transform'");
+                }
+                None => {
+                    let _ = writeln!(res, "  No transform applies");
+                }
+            }
+
+            if let Some((glob, pattern)) = self.permalink(&source)? {
+                let _ = writeln!(res, "  Path from permalink `{pattern}' for `{glob}'");
+            } else if destination != mirrored {
+                let _ = writeln!(
+                    res,
+                    "  Path from pretty urls, instead of '{}'",
+                    mirrored.display()
+                );
+            }
+
+            if self.is_layout_ignored(&destination, &mirrored) {
+                let _ = writeln!(res, "  No layout, as it is in `ignores.layout'");
+                continue;
+            }
+
+            let Some(layouts) = &self.layouts else {
+                let _ = writeln!(
+                    res,
+                    "  No layout, as '{}' does not exist",
+                    self.config.dirs.layout.display()
+                );
+                continue;
+            };
+
+            let _ = writeln!(res, "  Layouts tried:");
+
+            let mut found = false;
+            for candidate in self.layout_candidates(&mirrored)? {
+                if !found && layouts.contains(&candidate) {
+                    found = true;
+                    let _ = writeln!(res, "    '{}' (used)", candidate.display());
+                } else if layouts.contains(&candidate) {
+                    let _ = writeln!(res, "    '{}' (exists, not used)", candidate.display());
+                } else {
+                    let _ = writeln!(res, "    '{}' (does not exist)", candidate.display());
+                }
+            }
+
+            if !found {
+                let _ = writeln!(res, "  No layout applies");
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Copies the contents of the static dir to the output dir, along with the
+    /// asset manifest if fingerprinting is enabled. Returns the paths of the
+    /// files written.
+    fn copy_statics(&self) -> Result<Vec<PathBuf>> {
+        let mut res = Vec::new();
+
+        for asset in &self.statics {
+            let destination = self.static_destination(asset)?;
+            res.push(destination.clone());
+
+            if self.options.dry_run {
+                log::info!(
+                    "Would copy '{}' to '{}'{}",
+                    asset.display(),
+                    destination.display(),
+                    if destination.exists() {
+                        ", overwriting it"
+                    } else {
+                        ""
+                    }
+                );
+
+                continue;
+            }
 
             if destination.exists() {
                 log::debug!(
@@ -376,7 +1204,7 @@ impl Site {
                 })?;
             }
 
-            fs::copy(&asset, &destination).map_err(|source| Error::Io {
+            fs::copy(asset, &destination).map_err(|source| Error::Io {
                 msg: format!(
                     "Cannot copy static asset '{}' to '{}'",
                     asset.display(),
@@ -386,10 +1214,47 @@ impl Site {
             })?;
         }
 
-        Ok(())
+        if self.config.fingerprint.enable {
+            let path = self.config.dirs.out.join(&self.config.fingerprint.manifest);
+
+            self.manifest.write(&path, self.options.dry_run)?;
+            res.push(path);
+        }
+
+        Ok(res)
     }
 }
 
+/// A file a build writes, and how it is produced.
+#[derive(serde::Serialize)]
+pub(crate) struct Route {
+    /// Whether this is a page or a static asset.
+    pub(crate) kind: RouteKind,
+
+    /// The source page or static asset the file comes from.
+    pub(crate) source: PathBuf,
+
+    /// The path the file is written to.
+    pub(crate) destination: PathBuf,
+
+    /// The transform applied to the page, if any.
+    pub(crate) transform: Option<transform::Transform>,
+
+    /// The layout applied to the page, if any.
+    pub(crate) layout: Option<PathBuf>,
+}
+
+/// The kinds of files a build writes.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RouteKind {
+    /// A page rendered from the source directory.
+    Page,
+
+    /// An asset copied from the static directory.
+    Static,
+}
+
 /// Maps a rendered source template to a destination page via a transform.
 #[derive(serde::Serialize)]
 struct Mapping {
@@ -401,6 +1266,11 @@ struct Mapping {
     /// (eg `out/index.html`).
     destination: PathBuf,
 
+    /// The path mirroring the source path in the out directory (eg
+    /// `out/about.html`), before any url rewriting such as pretty urls. Layouts
+    /// are looked up relative to this path.
+    mirrored: PathBuf,
+
     /// The transform to apply to this page, if any is applicable.
     transform: Option<transform::Transform>,
 
@@ -410,6 +1280,14 @@ struct Mapping {
 }
 
 impl Mapping {
+    /// Returns true if this mapping's destination is an html page.
+    fn is_html(&self) -> bool {
+        matches!(
+            self.destination.extension().and_then(OsStr::to_str),
+            Some("html" | "htm")
+        )
+    }
+
     /// Applies this mapping's transform to its content, if one applies.
     pub fn transform(&mut self) -> Result<()> {
         if let Some(transform) = &self.transform {
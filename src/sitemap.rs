@@ -0,0 +1,143 @@
+//! Sitemap generation.
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{config, util, Error, Result};
+
+/// The maximum number of urls a single sitemap may contain, according to the
+/// sitemaps protocol. Sites with more pages get a sitemap index instead.
+const MAX_URLS: usize = 50_000;
+
+/// A page to be listed in the sitemap.
+pub(crate) struct Entry {
+    /// The root-relative url of the page (eg `/blog/`).
+    pub(crate) url: String,
+
+    /// When the page's source file was last modified, if known.
+    pub(crate) modified: Option<SystemTime>,
+}
+
+/// Returns the paths the sitemap for `entries` pages is written to: the
+/// configured path, plus a numbered sitemap for each chunk of pages if there
+/// are too many for one.
+pub(crate) fn paths(config: &config::Config, entries: usize) -> Vec<PathBuf> {
+    let path = config.dirs.out.join(&config.sitemap.path);
+
+    if entries <= MAX_URLS {
+        return vec![path];
+    }
+
+    let mut res = (0..entries.div_ceil(MAX_URLS))
+        .map(|i| chunk_path(&path, i))
+        .collect::<Vec<_>>();
+    res.push(path);
+
+    res
+}
+
+/// Returns the path of the `i`th (zero-based) numbered sitemap next to the
+/// sitemap index at `path`, eg `sitemap1.xml`.
+fn chunk_path(path: &Path, i: usize) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(format!("{}", i + 1));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+
+    path.with_file_name(name)
+}
+
+/// Writes the sitemap for the given pages to the path configured in
+/// `config.sitemap`, splitting it into several sitemaps and a sitemap index if
+/// there are too many pages. Returns the paths of the files written.
+pub(crate) fn write(
+    config: &config::Config,
+    entries: &[Entry],
+    dry_run: bool,
+) -> Result<Vec<PathBuf>> {
+    let base_url = config
+        .base_url
+        .as_deref()
+        .ok_or_else(|| Error::Config("`sitemap.enable` requires `base_url` to be set".into()))?
+        .trim_end_matches('/');
+
+    let path = config.dirs.out.join(&config.sitemap.path);
+
+    if entries.len() <= MAX_URLS {
+        write_file(&path, &urlset(base_url, entries), dry_run)?;
+
+        return Ok(vec![path]);
+    }
+
+    let mut res = Vec::new();
+
+    let mut index = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+
+    for (i, chunk) in entries.chunks(MAX_URLS).enumerate() {
+        let chunk_path = chunk_path(&path, i);
+
+        write_file(&chunk_path, &urlset(base_url, chunk), dry_run)?;
+        res.push(chunk_path.clone());
+
+        let _ = writeln!(
+            index,
+            "  <sitemap><loc>{}</loc></sitemap>",
+            util::xml_escape(
+                &(base_url.to_owned() + &util::page_url(&chunk_path, &config.dirs.out)?)
+            )
+        );
+    }
+
+    index.push_str("</sitemapindex>\n");
+
+    write_file(&path, &index, dry_run)?;
+    res.push(path);
+
+    Ok(res)
+}
+
+/// Returns the contents of a sitemap listing the given pages.
+fn urlset(base_url: &str, entries: &[Entry]) -> String {
+    let mut res = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+
+    for entry in entries {
+        let _ = write!(
+            res,
+            "  <url><loc>{}</loc>",
+            util::xml_escape(&(base_url.to_owned() + &entry.url))
+        );
+
+        if let Some(modified) = entry.modified {
+            let modified = chrono::DateTime::<chrono::Utc>::from(modified);
+            let _ = write!(res, "<lastmod>{}</lastmod>", modified.format("%Y-%m-%d"));
+        }
+
+        res.push_str("</url>\n");
+    }
+
+    res.push_str("</urlset>\n");
+
+    res
+}
+
+/// Writes a sitemap to `path`, unless this is a dry run.
+fn write_file(path: &Path, contents: &str, dry_run: bool) -> Result<()> {
+    log::info!(
+        "{} sitemap '{}'",
+        util::write_verb(path, dry_run),
+        path.display()
+    );
+
+    util::write_file(path, contents, dry_run)
+}
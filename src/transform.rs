@@ -10,7 +10,7 @@ use crate::{Error, Result};
 /// A transform is a command or pipeline of command for transforming content.
 /// Transforms take an input string on standard input and return an output
 /// string on standard output.
-#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 #[serde(untagged)]
 pub(crate) enum Transform {
     /// A transform with only one command.
@@ -37,6 +37,38 @@ pub(crate) fn walk_dir(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
     Ok(res)
 }
 
+/// Walks a directory like [walk_dir], but without following symlinks: a
+/// symlink is returned as a single path, even if it points to a directory.
+pub(crate) fn walk_dir_no_follow(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let read_dir = fs::read_dir(&dir).map_err(|source| Error::Io {
+        msg: format!("Cannot read directory '{}'", dir.as_ref().display()),
+        source,
+    })?;
+
+    let mut res = Vec::new();
+
+    for entry in read_dir {
+        let entry = entry.map_err(|source| Error::Io {
+            msg: format!("Cannot get entry in '{}'", dir.as_ref().display()),
+            source,
+        })?;
+
+        let path = entry.path();
+        let metadata = fs::symlink_metadata(&path).map_err(|source| Error::Io {
+            msg: format!("Cannot read metadata of '{}'", path.display()),
+            source,
+        })?;
+
+        if metadata.is_dir() {
+            res.append(&mut walk_dir_no_follow(path)?);
+        } else {
+            res.push(path);
+        }
+    }
+
+    Ok(res)
+}
+
 /// Strips the `old` prefix from a path and replaces it with `new`.
 pub(crate) fn swap_prefix(
     path: impl AsRef<Path>,
@@ -53,3 +85,215 @@ pub(crate) fn swap_prefix(
         })?;
     Ok(new.as_ref().join(stripped))
 }
+
+/// Converts a relative path into a `/`-separated string suitable for use in a
+/// URL, regardless of the platform's path separator.
+pub(crate) fn to_url_path(path: impl AsRef<Path>) -> Result<String> {
+    let mut parts = Vec::new();
+
+    for component in path.as_ref().components() {
+        parts.push(
+            component
+                .as_os_str()
+                .to_str()
+                .ok_or_else(|| Error::PathConversion(path.as_ref().to_owned()))?,
+        );
+    }
+
+    Ok(parts.join("/"))
+}
+
+/// Returns the SHA-256 digest of `contents` as a lowercase hex string.
+pub(crate) fn hex_digest(contents: &[u8]) -> String {
+    use sha2::Digest;
+
+    sha2::Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Returns the root-relative url (eg `/blog/post.html`) that the output file
+/// `destination` will be served at. `index.html` files are served at their
+/// directory's url (eg `/blog/`).
+pub(crate) fn page_url(destination: impl AsRef<Path>, out: impl AsRef<Path>) -> Result<String> {
+    let url = "/".to_owned() + &percent_encode(&to_url_path(swap_prefix(destination, out, "")?)?);
+
+    Ok(match url.strip_suffix("index.html") {
+        Some(dir) if dir.ends_with('/') => dir.to_owned(),
+        _ => url,
+    })
+}
+
+/// Returns the verb to log before writing `path`: `Writing`, or in a dry run
+/// `Would write` or `Would overwrite`, depending on whether `path` exists.
+pub(crate) fn write_verb(path: impl AsRef<Path>, dry_run: bool) -> &'static str {
+    match (dry_run, path.as_ref().exists()) {
+        (false, _) => "Writing",
+        (true, false) => "Would write",
+        (true, true) => "Would overwrite",
+    }
+}
+
+/// Writes `contents` to `path`, creating parent directories as needed. Does
+/// nothing in a dry run.
+pub(crate) fn write_file(
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+    dry_run: bool,
+) -> Result<()> {
+    let path = path.as_ref();
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if let Some(p) = path.parent() {
+        fs::create_dir_all(p).map_err(|source| Error::Io {
+            msg: format!("Cannot create '{}'", p.display()),
+            source,
+        })?;
+    }
+
+    fs::write(path, contents).map_err(|source| Error::Io {
+        msg: format!("Cannot write '{}'", path.display()),
+        source,
+    })
+}
+
+/// Removes every empty directory beneath `dir` (but not `dir` itself),
+/// including directories that only contain empty directories.
+pub(crate) fn remove_empty_dirs(dir: impl AsRef<Path>) -> Result<()> {
+    let dir = dir.as_ref();
+
+    let read_dir = fs::read_dir(dir).map_err(|source| Error::Io {
+        msg: format!("Cannot read directory '{}'", dir.display()),
+        source,
+    })?;
+
+    for entry in read_dir {
+        let entry = entry.map_err(|source| Error::Io {
+            msg: format!("Cannot get entry in '{}'", dir.display()),
+            source,
+        })?;
+
+        // don't follow symlinks to directories
+        if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+            continue;
+        }
+
+        let path = entry.path();
+
+        remove_empty_dirs(&path)?;
+
+        let is_empty = fs::read_dir(&path).is_ok_and(|mut entries| entries.next().is_none());
+
+        if is_empty {
+            log::debug!("Removing empty directory '{}'", path.display());
+
+            fs::remove_dir(&path).map_err(|source| Error::Io {
+                msg: format!("Cannot remove '{}'", path.display()),
+                source,
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Escapes the characters in `text` that are special in XML and HTML.
+pub(crate) fn xml_escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            c => res.push(c),
+        }
+    }
+
+    res
+}
+
+/// Percent-encodes every character in `path` that is not allowed in a url
+/// path.
+pub(crate) fn percent_encode(path: &str) -> String {
+    let mut res = String::with_capacity(path.len());
+
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/!$&'()*+,;=:@".contains(&b) {
+            res.push(b as char);
+        } else {
+            res.push_str(&format!("%{b:02X}"));
+        }
+    }
+
+    res
+}
+
+/// Parses a date in either RFC 3339 (`2024-01-31T12:00:00Z`) or plain
+/// `YYYY-MM-DD` format. Plain dates are taken to be at midnight UTC.
+pub(crate) fn parse_date(text: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let text = text.trim();
+
+    chrono::DateTime::parse_from_rfc3339(text)
+        .map(|date| date.to_utc())
+        .ok()
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc())
+        })
+}
+
+/// Returns the date at the start of a file's name, Jekyll-style (eg
+/// `2024-01-31-hello.md`), if there is one.
+pub(crate) fn date_prefix(path: impl AsRef<Path>) -> Option<chrono::DateTime<chrono::Utc>> {
+    let name = path.as_ref().file_name()?.to_str()?;
+
+    parse_date(name.get(..10)?)
+}
+
+/// Returns the publication date of a page, from a `<meta name="date">` (or
+/// `dcterms.date`) tag in the metadata in its `tokens` (see
+/// [html::metadata](crate::html::metadata)), or failing that the date prefix
+/// of its `source` file name.
+pub(crate) fn page_date(
+    source: impl AsRef<Path>,
+    tokens: &[crate::html::Token],
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    let tokens = crate::html::metadata(tokens);
+
+    crate::html::meta(tokens, "date")
+        .or_else(|| crate::html::meta(tokens, "dcterms.date"))
+        .and_then(|date| parse_date(&date))
+        .or_else(|| date_prefix(source))
+}
+
+/// Compiles a list of git-style glob patterns into a matcher. A pattern also
+/// matches everything beneath the paths it matches, so a directory like
+/// `src/blog` matches all of `src/blog/**`.
+pub(crate) fn glob_set<S: AsRef<str>>(patterns: &[S]) -> Result<globset::GlobSet> {
+    let mut builder = globset::GlobSetBuilder::new();
+
+    for pattern in patterns {
+        let pattern = pattern.as_ref().trim_end_matches('/');
+
+        for pattern in [pattern.to_owned(), format!("{pattern}/**")] {
+            builder.add(
+                globset::GlobBuilder::new(&pattern)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| Error::Config(format!("Invalid glob `{pattern}': {e}")))?,
+            );
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| Error::Config(format!("Invalid globs: {e}")))
+}